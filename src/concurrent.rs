@@ -0,0 +1,24 @@
+// Chapter 8 - Going concurrent: lock-free lists
+
+/* 
+    Every list so far needs `&mut self` to change, so sharing one between threads means wrapping it in a Mutex:
+    one thread at a time, everybody else waits. 
+    Lock-free lists get away with `&self` everywhere: all threads work on the list at once, 
+    and instead of locking, they race to swing a pointer with an atomic compare-and-swap (CAS). 
+    Whoever loses the race just tries again.
+
+    The linking part is surprisingly little code. The hard part is freeing memory:
+    a thread that pops a node can't just free it, since another thread may have loaded the very same pointer 
+    a moment earlier, and be about to read through it. That needs a memory reclamation scheme - see `hazard`.
+
+        hazard  - hazard pointers: "don't free this, I'm looking at it"
+        stack   - Treiber's stack, the lock-free counterpart of `second::List`
+        queue   - the Michael-Scott queue, the lock-free counterpart of `fifth::List`
+ */
+
+pub mod hazard;
+pub mod stack;
+pub mod queue;
+
+#[cfg(test)]
+mod test_util;
@@ -0,0 +1,312 @@
+// Chapter 8.1 - Hazard pointers
+
+/* 
+    The problem: thread A loads `head`, and is about to read `(*head).next`. 
+    Meanwhile, thread B pops that very node and frees it. A now reads freed memory. Boom.
+    (Even worse: the memory gets reused for a new node, which gets pushed, and A's CAS on `head` 
+    succeeds although the stack changed under its feet - the infamous ABA problem.)
+
+    Hazard pointers (Maged Michael, 2004) fix both with a simple protocol:
+        - a reader publishes the pointer it's about to use in a global, shared slot (its "hazard pointer")
+        - then it re-checks that the pointer is still reachable; if not, try again
+        - a thread that unlinks a node doesn't free it, it "retires" it
+        - every now and then, a thread scans all hazard pointers, and frees the retired nodes nobody is protecting
+
+    Once a node is unlinked, nobody can newly find it, so after the re-check no one can start protecting it anymore:
+    a node that isn't in any slot during the scan can never be read again.
+
+    Our implementation:
+        - the slots are `Record`s in a global linked list, which only ever grows; records are recycled, never freed
+        - every thread keeps its own list of retired nodes, and scans once it has collected "enough" of them
+        - when a thread exits, whatever it couldn't free yet gets handed over to a global orphanage,
+          which the next scan of any thread adopts
+ */
+
+use std::cell::RefCell;
+use std::ptr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+
+struct Record {
+    // the protected pointer, or null
+    hazard: AtomicPtr<()>,
+    // taken by some HazardPointer right now
+    active: AtomicBool,
+    // immutable once the record is published
+    next: *const Record,
+}
+
+// the global list of all records ever created
+static RECORDS: AtomicPtr<Record> = AtomicPtr::new(ptr::null_mut());
+static RECORD_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+fn records() -> impl Iterator<Item = &'static Record> {
+    let mut next = RECORDS.load(Ordering::Acquire).cast_const();
+    std::iter::from_fn(move || {
+        // SAFETY: records are never freed, and never change their `next` after being published
+        let record = unsafe { next.as_ref()? };
+        next = record.next;
+        Some(record)
+    })
+}
+
+/* 
+    One hazard pointer slot, owned by whoever holds this. 
+    Dropping it clears the slot and gives the record back for reuse.
+ */
+pub struct HazardPointer {
+    record: &'static Record,
+}
+
+impl HazardPointer {
+    pub fn new() -> Self {
+        // recycle an inactive record, if there is one
+        for record in records() {
+            if !record.active.load(Ordering::Relaxed)
+                && record.active.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_ok()
+            {
+                return HazardPointer { record };
+            }
+        }
+
+        // otherwise push a new one onto the global list - it stays there forever
+        let record = Box::leak(Box::new(Record {
+            hazard: AtomicPtr::new(ptr::null_mut()),
+            active: AtomicBool::new(true),
+            next: ptr::null(),
+        }));
+        let mut head = RECORDS.load(Ordering::Relaxed);
+        loop {
+            record.next = head;
+            match RECORDS.compare_exchange_weak(head, record, Ordering::Release, Ordering::Relaxed) {
+                Ok(_) => break,
+                Err(actual) => head = actual,
+            }
+        }
+        RECORD_COUNT.fetch_add(1, Ordering::Relaxed);
+        HazardPointer { record }
+    }
+
+    /* 
+        Loads `src`, and protects the loaded pointer, so that it won't be freed until `reset` (or drop).
+        The returned pointer was still stored in `src` AFTER it was protected,
+        so as long as everyone retires what they unlink from `src`, it's safe to dereference.
+     */
+    pub fn protect<T>(&self, src: &AtomicPtr<T>) -> *mut T {
+        let mut ptr = src.load(Ordering::Relaxed);
+        loop {
+            // SeqCst: the store must be visible to any scan that runs after our re-check below
+            self.record.hazard.store(ptr.cast(), Ordering::SeqCst);
+            let current = src.load(Ordering::SeqCst);
+            if current == ptr {
+                return ptr;
+            }
+            ptr = current;
+        }
+    }
+
+    pub fn reset(&self) {
+        self.record.hazard.store(ptr::null_mut(), Ordering::Release);
+    }
+}
+
+impl Default for HazardPointer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for HazardPointer {
+    fn drop(&mut self) {
+        self.reset();
+        self.record.active.store(false, Ordering::Release);
+    }
+}
+
+
+/* 
+    Retiring: a type-erased pointer plus the function that knows how to free it 
+    (same trick as the deferred drop queue of Chapter 4.3b).
+ */
+
+#[derive(Clone, Copy)]
+struct Retired {
+    ptr: *mut (),
+    free: unsafe fn(*mut ()),
+}
+
+// Retired nodes may be freed by whichever thread scans them. `retire`'s contract makes that OK.
+unsafe impl Send for Retired {}
+
+unsafe fn free_box<T>(ptr: *mut ()) {
+    drop(unsafe { Box::from_raw(ptr.cast::<T>()) });
+}
+
+// nodes left behind by threads that exited while somebody still protected them
+static ORPHANS: Mutex<Vec<Retired>> = Mutex::new(Vec::new());
+
+// how many retired nodes a thread collects before scanning, at the very least
+const SCAN_THRESHOLD: usize = 64;
+
+struct LocalRetired(Vec<Retired>);
+
+impl Drop for LocalRetired {
+    fn drop(&mut self) {
+        // last chance to clean up after ourselves; whatever is still protected goes to the orphanage
+        scan(&mut self.0);
+        if !self.0.is_empty() {
+            ORPHANS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).append(&mut self.0);
+        }
+    }
+}
+
+thread_local! {
+    static RETIRED: RefCell<LocalRetired> = const { RefCell::new(LocalRetired(Vec::new())) };
+}
+
+/// Frees `ptr` (as a `Box<T>`) as soon as no hazard pointer protects it anymore.
+///
+/// # Safety
+/// 
+/// The caller must guarantee that
+/// - `ptr` came from `Box::into_raw`, and is retired only once
+/// - `ptr` is no longer reachable from any shared location, so no one can start protecting it anymore
+/// - dropping the `Box<T>` is fine on any thread, at any later time
+pub unsafe fn retire<T>(ptr: *mut T) {
+    let retired = Retired { ptr: ptr.cast(), free: free_box::<T> };
+    let pushed = RETIRED.try_with(|local| {
+        let mut local = local.borrow_mut();
+        local.0.push(retired);
+        if local.0.len() >= SCAN_THRESHOLD.max(2 * RECORD_COUNT.load(Ordering::Relaxed)) {
+            scan(&mut local.0);
+        }
+    });
+    if pushed.is_err() {
+        // the thread is shutting down, and its list is gone already
+        ORPHANS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(retired);
+    }
+}
+
+// frees everything in `retired` that isn't protected, and keeps the rest
+fn scan(retired: &mut Vec<Retired>) {
+    // adopt the orphans first
+    if let Ok(mut orphans) = ORPHANS.try_lock() {
+        retired.append(&mut orphans);
+    }
+
+    // SeqCst pairs with `protect`: any hazard set before the node was unlinked is visible to us now
+    std::sync::atomic::fence(Ordering::SeqCst);
+    let mut protected: Vec<*mut ()> = records()
+        .map(|record| record.hazard.load(Ordering::SeqCst))
+        .filter(|hazard| !hazard.is_null())
+        .collect();
+    protected.sort_unstable();
+
+    retired.retain(|node| {
+        if protected.binary_search(&node.ptr).is_ok() {
+            true
+        } else {
+            // SAFETY: retired, so unreachable; and not protected, so nobody can be reading it
+            unsafe { (node.free)(node.ptr) };
+            false
+        }
+    });
+}
+
+// frees this thread's retired nodes (and any orphans) that aren't protected anymore, right now
+pub fn reclaim() {
+    let _ = RETIRED.try_with(|local| scan(&mut local.borrow_mut().0));
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::concurrent::hazard::{reclaim, retire, HazardPointer};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+    struct Counted(Arc<AtomicUsize>);
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn protected_nodes_survive() {
+        let freed = Arc::new(AtomicUsize::new(0));
+        let shared = AtomicPtr::new(Box::into_raw(Box::new(Counted(freed.clone()))));
+
+        let hazard = HazardPointer::new();
+        let ptr = hazard.protect(&shared);
+        // unlink and retire it, while it's still protected
+        shared.store(std::ptr::null_mut(), Ordering::SeqCst);
+        unsafe { retire(ptr) };
+
+        reclaim();
+        assert_eq!(freed.load(Ordering::SeqCst), 0);
+        // still readable
+        assert!(Arc::ptr_eq(unsafe { &(*ptr).0 }, &freed));
+
+        hazard.reset();
+        reclaim();
+        assert_eq!(freed.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn protect_follows_changes() {
+        let first = Box::into_raw(Box::new(1));
+        let second = Box::into_raw(Box::new(2));
+        let shared = AtomicPtr::new(first);
+
+        let hazard = HazardPointer::new();
+        assert_eq!(hazard.protect(&shared), first);
+        shared.store(second, Ordering::SeqCst);
+        assert_eq!(hazard.protect(&shared), second);
+        drop(hazard);
+
+        unsafe { retire(first) };
+        unsafe { retire(second) };
+        reclaim();
+    }
+
+    #[test]
+    fn orphans_get_adopted() {
+        let freed = Arc::new(AtomicUsize::new(0));
+        let shared = Arc::new(AtomicPtr::new(Box::into_raw(Box::new(Counted(freed.clone())))));
+
+        let hazard = HazardPointer::new();
+        let ptr = hazard.protect(&shared);
+
+        // another thread unlinks and retires it, and exits while we still protect it
+        let retirer = shared.clone();
+        std::thread::spawn(move || {
+            let ptr = retirer.swap(std::ptr::null_mut(), Ordering::SeqCst);
+            unsafe { retire(ptr) };
+        }).join().unwrap();
+        assert_eq!(freed.load(Ordering::SeqCst), 0);
+        assert_eq!(unsafe { (*ptr).0.load(Ordering::SeqCst) }, 0);
+
+        // once we let go, the next scan picks the orphan up - ours, or that of a test running in parallel
+        drop(hazard);
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while freed.load(Ordering::SeqCst) == 0 && std::time::Instant::now() < deadline {
+            reclaim();
+            std::thread::yield_now();
+        }
+        assert_eq!(freed.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn records_are_recycled() {
+        // lots of short-lived hazard pointers don't mean lots of records
+        let before = super::RECORD_COUNT.load(Ordering::SeqCst);
+        for _ in 0..1000 {
+            let hazard = HazardPointer::new();
+            hazard.reset();
+        }
+        // other tests may be running (and creating records) at the same time, but not a thousand of them
+        assert!(super::RECORD_COUNT.load(Ordering::SeqCst) - before < 100);
+    }
+}
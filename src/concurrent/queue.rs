@@ -0,0 +1,233 @@
+// Chapter 8.3 - The Michael-Scott queue: a lock-free `fifth::List`
+
+/* 
+    `fifth` pushes at the tail and pops at the head, with a separate pointer to each end.
+    Michael and Scott (1996) made exactly that design lock-free. Two tricks make it work:
+
+    1. A sentinel node. The queue is never really empty: `head` always points at a dummy node, 
+       and the actual elements start at `head.next`. So `head` and `tail` are never null, 
+       and pushers (who touch `tail` and `tail.next`) and poppers (who touch `head`) don't trip over each other 
+       on an empty queue, like `fifth` had to special-case.
+       Popping moves `head` one node forward: the node that held the popped element becomes the new sentinel.
+
+    2. A lagging tail. Pushing takes two steps: link the new node onto `tail.next`, then swing `tail` to it.
+       Between the two, another thread may come along and find `tail.next` isn't null. 
+       Instead of waiting for the first thread to finish, it finishes the job for it (swings `tail` forward), 
+       then retries. Nobody ever waits for anybody: that's what makes it lock-free.
+
+    Reclamation is the same as for the stack: old sentinels get retired, and every node gets protected 
+    by a hazard pointer before it's dereferenced. That also rules out ABA: a node we're looking at 
+    can't be freed, so its address can't come back as a "new" node while we're not looking.
+    Popping needs two hazard pointers, since it reads from both `head` and `head.next`.
+ */
+
+use std::marker::PhantomData;
+use std::mem::MaybeUninit;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+use super::hazard::{self, HazardPointer};
+
+pub struct Queue<T> {
+    // always the sentinel: the node BEFORE the first element
+    head: AtomicPtr<Node<T>>,
+    // the last node, or (briefly) the one before it
+    tail: AtomicPtr<Node<T>>,
+    _boo: PhantomData<T>,
+}
+
+struct Node<T> {
+    // uninit in the very first sentinel; moved out when a node becomes the sentinel
+    elem: MaybeUninit<T>,
+    next: AtomicPtr<Node<T>>,
+}
+
+impl<T> Node<T> {
+    fn new(elem: MaybeUninit<T>) -> *mut Self {
+        Box::into_raw(Box::new(Node { elem, next: AtomicPtr::new(ptr::null_mut()) }))
+    }
+}
+
+impl<T> Queue<T> {
+    pub fn new() -> Self {
+        let sentinel = Node::new(MaybeUninit::uninit());
+        Queue { head: AtomicPtr::new(sentinel), tail: AtomicPtr::new(sentinel), _boo: PhantomData }
+    }
+
+    pub fn push(&self, elem: T) {
+        let new = Node::new(MaybeUninit::new(elem));
+        let hazard = HazardPointer::new();
+        loop {
+            // tail only moves forward and never behind head, so the current tail is never retired
+            let tail = hazard.protect(&self.tail);
+            // SAFETY: protected
+            let next = unsafe { (*tail).next.load(Ordering::Acquire) };
+            if !next.is_null() {
+                // somebody linked a node but hasn't swung the tail yet: help them, then retry
+                let _ = self.tail.compare_exchange(tail, next, Ordering::Release, Ordering::Relaxed);
+                continue;
+            }
+            // step 1: link the new node after the last one
+            let linked = unsafe { (*tail).next.compare_exchange(next, new, Ordering::Release, Ordering::Relaxed) };
+            if linked.is_ok() {
+                // step 2: swing the tail. If that fails, somebody else already helped
+                let _ = self.tail.compare_exchange(tail, new, Ordering::Release, Ordering::Relaxed);
+                return;
+            }
+        }
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        let head_hazard = HazardPointer::new();
+        let next_hazard = HazardPointer::new();
+        loop {
+            let head = head_hazard.protect(&self.head);
+            // SAFETY: protected
+            let next = next_hazard.protect(unsafe { &(*head).next });
+            // if head is still the head, `next` is still reachable from it -> not retired, and safe to use
+            if self.head.load(Ordering::SeqCst) != head {
+                continue;
+            }
+            if next.is_null() {
+                // only the sentinel left
+                return None;
+            }
+            let tail = self.tail.load(Ordering::Acquire);
+            if head == tail {
+                // the tail lags behind: fix that before moving head past it
+                let _ = self.tail.compare_exchange(tail, next, Ordering::Release, Ordering::Relaxed);
+                continue;
+            }
+            if self.head.compare_exchange(head, next, Ordering::AcqRel, Ordering::Acquire).is_ok() {
+                // `next` is the new sentinel, and its element is ours - only the winner of the CAS gets here
+                let elem = unsafe { (*next).elem.assume_init_read() };
+                head_hazard.reset();
+                next_hazard.reset();
+                // SAFETY: the old sentinel is unreachable now, and only we unlinked it
+                unsafe { hazard::retire(head) };
+                return Some(elem);
+            }
+        }
+    }
+
+    // only a snapshot: another thread may push or pop right after
+    pub fn is_empty(&self) -> bool {
+        let hazard = HazardPointer::new();
+        let head = hazard.protect(&self.head);
+        // SAFETY: protected
+        unsafe { (*head).next.load(Ordering::Acquire).is_null() }
+    }
+}
+
+impl<T> Default for Queue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for Queue<T> {
+    fn drop(&mut self) {
+        // nobody else can touch the queue anymore: free the sentinel, and every node (and element) after it
+        let sentinel = unsafe { Box::from_raw(*self.head.get_mut()) };
+        let mut cur = sentinel.next.load(Ordering::Relaxed);
+        while !cur.is_null() {
+            let mut node = unsafe { Box::from_raw(cur) };
+            cur = *node.next.get_mut();
+            // everything after the sentinel still holds its element
+            unsafe { node.elem.assume_init_drop() };
+        }
+    }
+}
+
+// same as the stack: sharing the queue only ever moves T's between threads, it never shares them
+unsafe impl<T: Send> Send for Queue<T> {}
+unsafe impl<T: Send> Sync for Queue<T> {}
+
+
+#[cfg(test)]
+mod test {
+    use crate::concurrent::queue::Queue;
+    use crate::concurrent::test_util::{self, PRODUCERS};
+    use std::thread;
+
+    #[test]
+    fn basics() {
+        let queue = Queue::new();
+        assert_eq!(queue.pop(), None);
+        assert!(queue.is_empty());
+
+        queue.push(1); queue.push(2); queue.push(3);
+        assert!(!queue.is_empty());
+        assert_eq!(queue.pop(), Some(1));
+        assert_eq!(queue.pop(), Some(2));
+
+        queue.push(4); queue.push(5);
+        assert_eq!(queue.pop(), Some(3));
+        assert_eq!(queue.pop(), Some(4));
+        assert_eq!(queue.pop(), Some(5));
+        assert_eq!(queue.pop(), None);
+
+        // check the exhaustion case fixed the tail pointer
+        queue.push(6); queue.push(7);
+        assert_eq!(queue.pop(), Some(6));
+        assert_eq!(queue.pop(), Some(7));
+        assert_eq!(queue.pop(), None);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn auto_traits() {
+        test_util::auto_traits::<Queue<_>>();
+    }
+
+    #[test]
+    fn stress_producers_consumers() {
+        // FIFO per producer: every consumer sees each producer's elements in the order they were pushed
+        for sequence in test_util::producers_consumers::<Queue<_>>() {
+            let mut last_seen = [None; PRODUCERS];
+            for (producer, i) in sequence {
+                assert!(last_seen[producer] < Some(i), "producer {}: {} after {:?}", producer, i, last_seen[producer]);
+                last_seen[producer] = Some(i);
+            }
+        }
+    }
+
+    #[test]
+    fn stress_mixed() {
+        test_util::mixed::<Queue<_>>();
+    }
+
+    #[test]
+    fn lagging_tail() {
+        /*
+            Every thread pushes and immediately pops, so the queue keeps going from empty to one element and back:
+            the tail lags behind all the time, and pops keep retiring the node it (briefly) still points to.
+            Afterwards, the tail has to be back on the last node, or the next push would link onto a freed one.
+         */
+        const THREADS: usize = 16;
+        const OPS: usize = 10_000;
+
+        let queue = Queue::new();
+        thread::scope(|s| {
+            for t in 0..THREADS {
+                let queue = &queue;
+                s.spawn(move || {
+                    for i in 0..OPS {
+                        queue.push(t * OPS + i);
+                        assert!(queue.pop().is_some(), "at least our own push is still there");
+                    }
+                });
+            }
+        });
+        assert!(queue.is_empty());
+        queue.push(1); queue.push(2);
+        assert_eq!(queue.pop(), Some(1));
+        assert_eq!(queue.pop(), Some(2));
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn long_drop() {
+        test_util::long_drop::<Queue<_>>();
+    }
+}
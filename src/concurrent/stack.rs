@@ -0,0 +1,205 @@
+// Chapter 8.2 - Treiber's stack: a lock-free `second::List`
+
+/* 
+    Same layout as `second::List`: a head pointer, and nodes that point to the next one down.
+    Only now, `head` is an AtomicPtr, and all operations take `&self`:
+
+    push:   make a node that points to the current head, then CAS head from "current head" to "new node".
+            If somebody else changed head in the meantime, the CAS fails: repoint the node, try again.
+    pop:    load head, read its `next`, then CAS head from "that node" to "its next".
+            Again, if somebody got in between, try again.
+
+    That's it (R. Kent Treiber, 1986). The catch is all in pop: between loading head and reading `(*head).next`,
+    another thread may pop and free that node. So pop protects the node with a hazard pointer first (Chapter 8.1),
+    and retires nodes instead of freeing them. As a bonus, a protected node can't be freed and reused, so no ABA either.
+
+    Elements are moved out of a node as soon as it's popped, but the node itself may live on for a while 
+    (somebody may still be reading its `next`) - so they're kept in a ManuallyDrop, 
+    and freeing a retired node only ever frees the memory.
+ */
+
+use std::marker::PhantomData;
+use std::mem::ManuallyDrop;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+use super::hazard::{self, HazardPointer};
+
+pub struct Stack<T> {
+    head: AtomicPtr<Node<T>>,
+    // AtomicPtr is Send + Sync no matter what it points to, but we own T's (see Send/Sync below)
+    _boo: PhantomData<T>,
+}
+
+struct Node<T> {
+    elem: ManuallyDrop<T>,
+    // only written before the node is published
+    next: *mut Node<T>,
+}
+
+impl<T> Stack<T> {
+    pub fn new() -> Self {
+        Stack { head: AtomicPtr::new(ptr::null_mut()), _boo: PhantomData }
+    }
+
+    pub fn push(&self, elem: T) {
+        let new = Box::into_raw(Box::new(Node { elem: ManuallyDrop::new(elem), next: ptr::null_mut() }));
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            // SAFETY: nobody else can see `new` before the CAS succeeds
+            unsafe { (*new).next = head };
+            // Release: whoever loads `new` from head also sees its element and `next`
+            match self.head.compare_exchange_weak(head, new, Ordering::Release, Ordering::Relaxed) {
+                Ok(_) => return,
+                Err(actual) => head = actual,
+            }
+        }
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        let hazard = HazardPointer::new();
+        loop {
+            let head = hazard.protect(&self.head);
+            if head.is_null() {
+                return None;
+            }
+            // SAFETY: protected, and it was still the head after protecting it -> not freed (yet, or at all)
+            let next = unsafe { (*head).next };
+            if self.head.compare_exchange(head, next, Ordering::AcqRel, Ordering::Acquire).is_ok() {
+                // we unlinked it, so the element is ours. Other threads may still read `next`, but never `elem`
+                let elem = unsafe { ptr::read(&*(*head).elem) };
+                hazard.reset();
+                // SAFETY: unlinked by us (and only us), allocated by push
+                unsafe { hazard::retire(head) };
+                return Some(elem);
+            }
+        }
+    }
+
+    /* 
+        We can't hand out a `&T` to the top element: another thread may pop it (and drop it) any time.
+        A copy is the best we can do. It has to be Copy, not just Clone: a popper moves the element out
+        while we may still be reading it, and only a Copy type stays valid after being moved out of.
+     */
+    pub fn peek(&self) -> Option<T>
+    where
+        T: Copy,
+    {
+        let hazard = HazardPointer::new();
+        let head = hazard.protect(&self.head);
+        // SAFETY: protected, so the node (and the bytes of its element) stay around until `hazard` is dropped
+        unsafe { head.as_ref().map(|node| *node.elem) }
+    }
+
+    // only a snapshot, of course: by the time you look at the answer, it may have changed
+    pub fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Acquire).is_null()
+    }
+}
+
+impl<T> Default for Stack<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for Stack<T> {
+    fn drop(&mut self) {
+        // &mut self: nobody else is using the stack, so no need for hazard pointers, just free everything
+        let mut cur = *self.head.get_mut();
+        while !cur.is_null() {
+            // SAFETY: still linked, so it was never retired
+            let mut node = unsafe { Box::from_raw(cur) };
+            cur = node.next;
+            unsafe { ManuallyDrop::drop(&mut node.elem) };
+        }
+    }
+}
+
+/* 
+    Send: sending the stack sends the T's.
+    Sync: sharing the stack lets any thread push and pop, i.e. move T's between threads -> T: Send is all we need, 
+    nobody ever gets a `&T` (peek only copies, and Copy + Send is fine too).
+ */
+unsafe impl<T: Send> Send for Stack<T> {}
+unsafe impl<T: Send> Sync for Stack<T> {}
+
+
+#[cfg(test)]
+mod test {
+    use crate::concurrent::stack::Stack;
+    use crate::concurrent::test_util;
+    use std::thread;
+
+    #[test]
+    fn basics() {
+        let stack = Stack::new();
+        assert_eq!(stack.pop(), None);
+        assert!(stack.is_empty());
+
+        stack.push(1); stack.push(2); stack.push(3);
+        assert!(!stack.is_empty());
+        assert_eq!(stack.pop(), Some(3));
+        assert_eq!(stack.pop(), Some(2));
+
+        stack.push(4); stack.push(5);
+        assert_eq!(stack.pop(), Some(5));
+        assert_eq!(stack.pop(), Some(4));
+        assert_eq!(stack.pop(), Some(1));
+        assert_eq!(stack.pop(), None);
+    }
+
+    #[test]
+    fn peek() {
+        let stack = Stack::new();
+        assert_eq!(stack.peek(), None);
+        stack.push(1); stack.push(2);
+        assert_eq!(stack.peek(), Some(2));
+        stack.pop();
+        assert_eq!(stack.peek(), Some(1));
+    }
+
+    #[test]
+    fn auto_traits() {
+        test_util::auto_traits::<Stack<_>>();
+    }
+
+    #[test]
+    fn stress_producers_consumers() {
+        test_util::producers_consumers::<Stack<_>>();
+    }
+
+    #[test]
+    fn stress_mixed() {
+        test_util::mixed::<Stack<_>>();
+    }
+
+    #[test]
+    fn stress_peek() {
+        // every thread peeks at whatever is on top while the others push and pop
+        const THREADS: usize = 8;
+        const OPS: usize = 20_000;
+
+        let stack = Stack::new();
+        thread::scope(|s| {
+            for _ in 0..THREADS {
+                let stack = &stack;
+                s.spawn(move || {
+                    for i in 0..OPS {
+                        stack.push(i);
+                        assert!(stack.peek().is_some(), "at least our own push is still there");
+                        if i % 3 != 0 {
+                            stack.pop();
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(std::iter::from_fn(|| stack.pop()).count(), THREADS * OPS.div_ceil(3));
+    }
+
+    #[test]
+    fn long_drop() {
+        test_util::long_drop::<Stack<_>>();
+    }
+}
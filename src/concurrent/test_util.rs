@@ -0,0 +1,130 @@
+// Shared tests for the lock-free lists
+
+/*
+    Whatever order they hand elements back in, the stack and the queue make the same promises:
+    any number of threads can push and pop at once, and no element ever gets lost, duplicated or leaked.
+    So those checks are written once, against the little `LockFree` trait, and run for both of them.
+    What's left in stack.rs and queue.rs is only what's specific to each (LIFO, FIFO, the queue's lagging tail).
+ */
+
+use crate::concurrent::queue::Queue;
+use crate::concurrent::stack::Stack;
+use crate::test_util::DropTracker;
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+
+// Send + Sync, so an impl for `L<T>` only exists if `L<T>` really can be shared between threads
+pub trait LockFree<T>: Default + Send + Sync {
+    fn push(&self, elem: T);
+    fn pop(&self) -> Option<T>;
+    fn is_empty(&self) -> bool;
+}
+
+impl<T: Send> LockFree<T> for Stack<T> {
+    fn push(&self, elem: T) { self.push(elem) }
+    fn pop(&self) -> Option<T> { self.pop() }
+    fn is_empty(&self) -> bool { self.is_empty() }
+}
+
+impl<T: Send> LockFree<T> for Queue<T> {
+    fn push(&self, elem: T) { self.push(elem) }
+    fn pop(&self) -> Option<T> { self.pop() }
+    fn is_empty(&self) -> bool { self.is_empty() }
+}
+
+// a Cell can't be shared, but handing it from thread to thread through the list is fine:
+// this only compiles if `L` is Send + Sync for a T that's Send but not Sync
+pub fn auto_traits<L: LockFree<Cell<i32>>>() {}
+
+pub const PRODUCERS: usize = 8;
+pub const PER_PRODUCER: usize = 20_000;
+const CONSUMERS: usize = 8;
+
+/*
+    PRODUCERS threads push `(producer, i)` for i in 0..PER_PRODUCER, while CONSUMERS threads pop.
+    Checks that every element came out exactly once,
+    and returns what every consumer popped, in the order it popped them.
+ */
+pub fn producers_consumers<L: LockFree<(usize, usize)>>() -> Vec<Vec<(usize, usize)>> {
+    let list = L::default();
+    let popped: Vec<AtomicUsize> = (0..PRODUCERS * PER_PRODUCER).map(|_| AtomicUsize::new(0)).collect();
+    let producers_done = AtomicBool::new(false);
+
+    let sequences = thread::scope(|s| {
+        let consumers: Vec<_> = (0..CONSUMERS).map(|_| s.spawn(|| {
+            let mut sequence = Vec::new();
+            loop {
+                match list.pop() {
+                    Some((producer, i)) => {
+                        popped[producer * PER_PRODUCER + i].fetch_add(1, Ordering::Relaxed);
+                        sequence.push((producer, i));
+                    }
+                    None if producers_done.load(Ordering::SeqCst) => break,
+                    None => thread::yield_now(),
+                }
+            }
+            sequence
+        })).collect();
+
+        let producers: Vec<_> = (0..PRODUCERS).map(|producer| {
+            let list = &list;
+            s.spawn(move || {
+                for i in 0..PER_PRODUCER {
+                    list.push((producer, i));
+                }
+            })
+        }).collect();
+        for producer in producers {
+            producer.join().unwrap();
+        }
+        producers_done.store(true, Ordering::SeqCst);
+        consumers.into_iter().map(|consumer| consumer.join().unwrap()).collect()
+    });
+
+    // nothing lost, nothing duplicated
+    for (elem, count) in popped.iter().enumerate() {
+        assert_eq!(count.load(Ordering::Relaxed), 1, "element {} popped {} times", elem, count.load(Ordering::Relaxed));
+    }
+    assert!(list.is_empty());
+    sequences
+}
+
+/*
+    Every thread both pushes and pops, keeping the list almost empty,
+    then whatever is left gets dropped with the list.
+    Every element has to be dropped exactly once: either right after it was popped, or by the list's Drop.
+ */
+pub fn mixed<L: LockFree<crate::test_util::DropCounter>>() {
+    const THREADS: usize = 16;
+    const OPS: usize = 10_000;
+
+    let tracker = DropTracker::new(THREADS * OPS);
+    let list = L::default();
+    thread::scope(|s| {
+        for t in 0..THREADS {
+            let (list, tracker) = (&list, &tracker);
+            s.spawn(move || {
+                for i in 0..OPS {
+                    list.push(tracker.counter(t * OPS + i));
+                    if i % 4 != 0 {
+                        // dropping the popped element is part of the test
+                        assert!(list.pop().is_some(), "at least our own push is still there");
+                    }
+                }
+            });
+        }
+    });
+    drop(list);
+    tracker.assert_all_dropped_once();
+}
+
+pub fn long_drop<L: LockFree<usize> + 'static>() {
+    crate::test_util::drop_on_small_stack(|| {
+        let list = L::default();
+        for elem in 0..crate::test_util::LONG {
+            list.push(elem);
+        }
+        list
+    });
+}
@@ -170,10 +170,255 @@
  /* 
     Chapter 6.5 : Stacked Borrows - the issue found be miri in Ch6.4 for the code from Ch6.3
 
+    Miri complained about our Box-based queue: we kept a raw `tail` pointer into a Node owned by a Box,
+    and then went on using the Box (and references derived from it) to get at that very same Node.
     
+    Stacked Borrows models every piece of memory as owning a "borrow stack":
+        - creating a reference/pointer from another one pushes a new item on top of the stack
+        - using any pointer pops everything ABOVE it off the stack (those borrows are now dead)
+        - using a pointer that is no longer on the stack is Undefined Behaviour
+    
+    Box<T> asserts unique ownership, just like &mut. So every time we touched `self.head` (the Box), 
+    our raw `tail` pointer was popped off the borrow stack, and the next `push` wrote through a dead pointer. Oops.
 
+    The fix is simple in principle, and the rule of thumb from the book goes:
+        Once you start using raw pointers, try to ONLY use raw pointers.
+    
+    So our Links become raw pointers as well: 
+        - allocate nodes with `Box::new`, and immediately turn them into raw pointers with `Box::into_raw`
+        - from then on, only ever access nodes through raw pointers
+        - when popping (or dropping), turn the raw pointer back into a Box with `Box::from_raw`, so it gets freed properly
+    
+    Since `Box::into_raw` hands out the one true "root" pointer, all our head/tail/next copies are siblings 
+    derived from it, and using one of them never invalidates the others. Miri is happy, we are happy.
   */
 
+// Chapter 6.6 : Testing Stacked Borrows -> the final, Miri-approved layout
+
+use std::ptr::NonNull;
+
+/// The Miri-approved queue.
+///
+/// Its links are `NonNull`s rather than `*mut`s (see Chapter 6.8), so `List<T>` is covariant over `T`, 
+/// and it owns its elements like a `Box<T>` would: it's `Send`/`Sync` exactly when `T` is.
+///
+/// ```compile_fail
+/// // Rc isn't Send, so a queue of Rc's can't be either
+/// use std::rc::Rc;
+/// use tutorial_too_many_lists::fifth::List;
+///
+/// let mut list = List::new();
+/// list.push(Rc::new(1));
+/// std::thread::spawn(move || drop(list));
+/// ```
+///
+/// ```compile_fail
+/// // Cell isn't Sync, so a queue of Cells can't be shared between threads
+/// use std::cell::Cell;
+/// use tutorial_too_many_lists::fifth::List;
+///
+/// let mut list = List::new();
+/// list.push(Cell::new(1));
+/// std::thread::scope(|s| { s.spawn(|| list.peek().map(Cell::get)); });
+/// ```
+pub struct List<T> {
+    head: Link<T>,
+    tail: Link<T>,
+}
+
+// None == empty link
+type Link<T> = Option<NonNull<Node<T>>>;
+
+struct Node<T> {
+    elem: T,
+    next: Link<T>,
+}
+
+impl<T> List<T> {
+    pub fn new() -> Self {
+        List { head: None, tail: None }
+    }
+
+    pub fn push(&mut self, elem: T) {
+        unsafe {
+            // turn the Box into a raw pointer right away; we never touch the Box again
+            let new_tail = NonNull::new_unchecked(Box::into_raw(Box::new(Node {
+                elem,
+                next: None,
+            })));
+
+            if let Some(old_tail) = self.tail {
+                // non-empty list -> hook the new node onto the old tail
+                (*old_tail.as_ptr()).next = Some(new_tail);
+            } else {
+                // empty list -> the new node is also the head
+                self.head = Some(new_tail);
+            }
+
+            self.tail = Some(new_tail);
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        unsafe {
+            self.head.map(|node| {
+                // take ownership back from the raw pointer, so the node is freed at the end of this scope
+                let head = Box::from_raw(node.as_ptr());
+                self.head = head.next;
+
+                // if we're out of heads, make sure the tail goes too
+                if self.head.is_none() {
+                    self.tail = None;
+                }
+
+                head.elem
+            })
+        }
+    }
+
+    // peeking only hands out references derived from the raw pointer, 
+    // which are bound to the lifetime of the borrow of self
+    pub fn peek(&self) -> Option<&T> {
+        unsafe {
+            self.head.map(|node| &(*node.as_ptr()).elem)
+        }
+    }
+
+    pub fn peek_mut(&mut self) -> Option<&mut T> {
+        unsafe {
+            self.head.map(|node| &mut (*node.as_ptr()).elem)
+        }
+    }
+}
+
+impl<T> Default for List<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for List<T> {
+    fn drop(&mut self) {
+        // pop converts each node back into a Box, which then gets dropped 
+        while self.pop().is_some() {}
+    }
+}
+
+
+// Chapter 6.7 : Extra Junk - Iteration
+/* 
+    Now that push and pop are Miri-approved, the iterators are basically the ones from Chapter 3 again.
+    The only difference: our links are raw pointers now, so we turn them into (optional) references 
+    with `NonNull::as_ref` / `NonNull::as_mut`.
+
+    The references we hand out are bound to the borrow of the List, so Stacked Borrows stays happy:
+    nobody can push/pop (and thereby invalidate our references) while an Iter/IterMut is alive.
+ */
+
+pub struct IntoIter<T>(List<T>);
+
+pub struct Iter<'a, T> {
+    next: Option<&'a Node<T>>,
+}
+
+/// Like `&'a mut T`, `IterMut` is invariant over `T`: 
+/// otherwise it could be used to write short-lived values into a list of long-lived ones.
+///
+/// ```compile_fail
+/// use tutorial_too_many_lists::fifth::IterMut;
+///
+/// fn shorten<'i, 'a>(iter: IterMut<'i, &'static str>) -> IterMut<'i, &'a str> {
+///     iter
+/// }
+/// ```
+pub struct IterMut<'a, T> {
+    next: Option<&'a mut Node<T>>,
+}
+
+impl<T> List<T> {
+    #[allow(clippy::should_implement_trait)]
+    pub fn into_iter(self) -> IntoIter<T> {
+        IntoIter(self)
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        unsafe {
+            Iter { next: self.head.map(|node| node.as_ref()) }
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        unsafe {
+            IterMut { next: self.head.map(|mut node| node.as_mut()) }
+        }
+    }
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.pop()
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        unsafe {
+            self.next.map(|node| {
+                self.next = node.next.map(|next| next.as_ref());
+                &node.elem
+            })
+        }
+    }
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        unsafe {
+            // same trick as in Chapter 3: take the exclusive reference out, so only we hold it
+            self.next.take().map(|node| {
+                self.next = node.next.map(|mut next| next.as_mut());
+                &mut node.elem
+            })
+        }
+    }
+}
+
+
+// Chapter 6.8 : Variance and Send/Sync
+/* 
+    Two things the compiler quietly decided about our raw-pointer queue, both of them needlessly strict:
+
+    1. Variance. `*mut T` is INVARIANT over T (writing through it must not be allowed to sneak in a shorter-lived T),
+       and so was every struct containing one. A `List<&'static str>` couldn't be passed where a `List<&'a str>` 
+       was expected - something any Box- or Vec-based collection can do. Even Iter was affected, 
+       since it points at Nodes, and the Nodes contained *mut's as well.
+       NonNull<T> is a `*mut T` that is covariant (and never null, so Option<NonNull<T>> is the size of a pointer).
+       Covariance is only sound because we never hand out a way to write a T through a shared `&List<T>` -
+       which is exactly the contract of every other owning collection.
+
+    2. Send and Sync. Raw pointers (NonNull included) are neither, so neither was our List - 
+       the compiler can't know what they point at. We do: the List owns its nodes exactly like a Box owns its contents.
+       So we promise (`unsafe impl`) that the List is as thread-safe as the T's inside of it.
+       Iter and IterMut are just references into the List, so they are as thread-safe as &T and &mut T 
+       once the Nodes are - and the Nodes only contain a T and a link we never touch through a shared reference.
+ */
+
+unsafe impl<T: Send> Send for List<T> {}
+unsafe impl<T: Sync> Sync for List<T> {}
+
+unsafe impl<T: Send> Send for Node<T> {}
+unsafe impl<T: Sync> Sync for Node<T> {}
+
+
+// Chapter 6.9 : Putting the queue to work - a blocking channel (see channel.rs)
+
+pub mod channel;
+
 
 #[cfg(test)]
 mod test {
@@ -204,5 +449,180 @@ mod test {
         assert_eq!(list.pop(), Some(5));
         assert_eq!(list.pop(), None);
         assert_eq!(list.pop(), None);
+
+        // check the exhaustion case fixed the pointer right
+        list.push(6); list.push(7);
+
+        // check normal removal
+        assert_eq!(list.pop(), Some(6));
+        assert_eq!(list.pop(), Some(7));
+        assert_eq!(list.pop(), None);
+    }
+
+    #[test]
+    fn peek() {
+        let mut list = List::new();
+        assert_eq!(list.peek(), None);
+        assert_eq!(list.peek_mut(), None);
+
+        list.push(1); list.push(2); list.push(3);
+        // queue -> peek looks at the oldest element
+        assert_eq!(list.peek(), Some(&1));
+        assert_eq!(list.peek_mut(), Some(&mut 1));
+
+        if let Some(value) = list.peek_mut() {
+            *value = 42;
+        }
+        assert_eq!(list.peek(), Some(&42));
+        assert_eq!(list.pop(), Some(42));
+        assert_eq!(list.peek(), Some(&2));
+    }
+
+    #[test]
+    fn miri_food() {
+        // interleave pushes, pops and peeks to give Miri something to chew on
+        let mut list = List::new();
+
+        list.push(1); list.push(2); list.push(3);
+
+        assert!(list.pop() == Some(1));
+        list.push(4);
+        assert!(list.pop() == Some(2));
+        list.push(5);
+
+        assert!(list.peek() == Some(&3));
+        list.push(6);
+        if let Some(x) = list.peek_mut() { *x *= 10; }
+        assert!(list.peek() == Some(&30));
+        assert!(list.pop() == Some(30));
+
+        list.push(7);
+        assert!(list.pop() == Some(4));
+        // leave some elements in the list, so Drop has to clean up after us
+
+        // mix iteration into the pushing and popping
+        list.push(8);
+        for elem in list.iter_mut() {
+            *elem *= 100;
+        }
+
+        let mut iter = list.iter();
+        assert_eq!(iter.next(), Some(&500));
+        assert_eq!(iter.next(), Some(&600));
+        assert_eq!(iter.next(), Some(&700));
+        assert_eq!(iter.next(), Some(&800));
+        assert_eq!(iter.next(), None);
+
+        assert!(list.pop() == Some(500));
+        if let Some(x) = list.peek_mut() { *x += 1; }
+        assert!(list.peek() == Some(&601));
+
+        let mut iter = list.into_iter();
+        assert_eq!(iter.next(), Some(601));
+        assert_eq!(iter.next(), Some(700));
+        // leave one element behind in the IntoIter as well
+    }
+
+    #[test]
+    fn into_iter() {
+        let mut list = List::new();
+        list.push(1); list.push(2); list.push(3);
+
+        let mut iter = list.into_iter();
+        assert_eq!(iter.next(), Some(1));
+        assert_eq!(iter.next(), Some(2));
+        assert_eq!(iter.next(), Some(3));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn iter() {
+        let mut list = List::new();
+        list.push(1); list.push(2); list.push(3);
+
+        let mut iter = list.iter();
+        assert_eq!(iter.next(), Some(&1));
+        assert_eq!(iter.next(), Some(&2));
+        assert_eq!(iter.next(), Some(&3));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn iter_mut() {
+        let mut list = List::new();
+        list.push(1); list.push(2); list.push(3);
+
+        let mut iter = list.iter_mut();
+        assert_eq!(iter.next(), Some(&mut 1));
+        if let Some(value) = iter.next() { *value = 42 }
+        assert_eq!(iter.next(), Some(&mut 3));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next(), None);
+
+        let mut iter = list.iter_mut();
+        assert_eq!(iter.next(), Some(&mut 1));
+        assert_eq!(iter.next(), Some(&mut 42));
+        assert_eq!(iter.next(), Some(&mut 3));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn variance_and_auto_traits() {
+        use crate::fifth::{IntoIter, Iter, IterMut};
+        use crate::test_util::{assert_covariant, assert_send_sync};
+
+        // IterMut can't be covariant, see its docs
+        assert_covariant!(List<T>, Iter<'i, T>, IntoIter<T>);
+        assert_send_sync::<List<i32>>();
+        assert_send_sync::<IntoIter<i32>>();
+        assert_send_sync::<Iter<'_, i32>>();
+        assert_send_sync::<IterMut<'_, i32>>();
+
+        // and it actually crosses threads
+        let mut list = List::new();
+        list.push(1); list.push(2);
+        let list = std::thread::spawn(move || { list.push(3); list }).join().unwrap();
+        std::thread::scope(|s| { s.spawn(|| assert_eq!(list.iter().sum::<i32>(), 6)); });
+    }
+
+    #[test]
+    fn long_drop() {
+        crate::test_util::drop_on_small_stack(|| {
+            let mut list = List::new();
+            for elem in 0..crate::test_util::LONG {
+                list.push(elem);
+            }
+            list
+        });
+    }
+
+    #[test]
+    fn no_leaks() {
+        use crate::test_util::{assert_no_leaks, DropTracker};
+
+        let tracker = DropTracker::new(6);
+        assert_no_leaks(|| {
+            let mut list = List::new();
+            list.push(tracker.counter(0));
+            list.push(tracker.counter(1));
+            list.push(tracker.counter(2));
+            assert_eq!(list.pop().map(|elem| elem.id()), Some(0));
+            assert_eq!(list.iter().count(), 2);
+            assert_eq!(list.iter_mut().count(), 2);
+            list.push(tracker.counter(3));
+            assert_eq!(list.peek().map(|elem| elem.id()), Some(1));
+
+            let mut iter = list.into_iter();
+            iter.next();
+            drop(iter);
+
+            let mut list = List::new();
+            list.push(tracker.counter(4));
+            list.push(tracker.counter(5));
+        });
+        tracker.assert_all_dropped_once();
     }
 }
\ No newline at end of file
@@ -0,0 +1,565 @@
+// Chapter 6.9 - A blocking, bounded channel on top of our queue
+
+/* 
+    A channel is just a queue that threads share: senders push at the back, receivers pop at the front.
+    Our queue needs `&mut self` for that, so it goes behind a Mutex, and everyone takes turns.
+    
+    What makes it a channel instead of a `Mutex<List<T>>` is the waiting:
+        - a receiver that finds the queue empty sleeps until there's something to take
+        - the queue is bounded: a sender that finds it full sleeps until there's room again (backpressure - 
+          a fast producer can't bury a slow consumer in messages)
+    Sleeping and waking up is what a Condvar is for: `wait` releases the lock and sleeps, 
+    `notify_*` wakes the sleepers up, who then re-take the lock and check again. 
+    Two of them here: one for "not empty anymore" (receivers wait on it), one for "not full anymore" (senders do).
+
+    The other thing a channel has to know is when to give up:
+        - all Senders gone -> nothing will ever arrive again: receivers get the rest of the queue, then errors
+        - all Receivers gone -> nobody will ever read: senders get an error (and their message back)
+    Both ends count their live handles in the shared state, and the last one out wakes everybody up.
+
+    Same vocabulary as std's `mpsc::sync_channel`, except that both ends can be cloned (many producers, many consumers),
+    and there's no rendezvous (zero capacity) mode.
+ */
+
+use std::error::Error;
+use std::fmt;
+use std::mem;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use super::List;
+
+struct State<T> {
+    queue: List<T>,
+    // the queue doesn't count its elements, so we do it for it
+    len: usize,
+    senders: usize,
+    receivers: usize,
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    capacity: usize,
+    not_empty: Condvar,
+    not_full: Condvar,
+}
+
+impl<T> Shared<T> {
+    // nothing panics while holding the lock, but if something ever does, the state is still consistent
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+// a channel that holds at most `capacity` messages at once; panics if that's 0
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "channel capacity must be at least 1");
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State { queue: List::new(), len: 0, senders: 1, receivers: 1 }),
+        capacity,
+        not_empty: Condvar::new(),
+        not_full: Condvar::new(),
+    });
+    (Sender { shared: shared.clone() }, Receiver { shared })
+}
+
+
+// Sending
+
+impl<T> Sender<T> {
+    // blocks while the channel is full; fails (handing the message back) if all receivers are gone
+    pub fn send(&self, elem: T) -> Result<(), SendError<T>> {
+        let mut state = self.shared.lock();
+        loop {
+            if state.receivers == 0 {
+                return Err(SendError(elem));
+            }
+            if state.len < self.shared.capacity {
+                break;
+            }
+            state = self.shared.not_full.wait(state).unwrap_or_else(PoisonError::into_inner);
+        }
+        state.queue.push(elem);
+        state.len += 1;
+        drop(state);
+        self.shared.not_empty.notify_one();
+        Ok(())
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.lock().senders += 1;
+        Sender { shared: self.shared.clone() }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.senders -= 1;
+        if state.senders == 0 {
+            drop(state);
+            // wake up every waiting receiver, so they can notice
+            self.shared.not_empty.notify_all();
+        }
+    }
+}
+
+
+// Receiving
+
+impl<T> Receiver<T> {
+    // blocks until a message arrives; fails once the channel is empty and all senders are gone
+    pub fn recv(&self) -> Result<T, RecvError> {
+        let mut state = self.shared.lock();
+        loop {
+            if let Some(elem) = self.take(&mut state) {
+                return Ok(elem);
+            }
+            if state.senders == 0 {
+                return Err(RecvError);
+            }
+            state = self.shared.not_empty.wait(state).unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    // never blocks
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        let mut state = self.shared.lock();
+        match self.take(&mut state) {
+            Some(elem) => Ok(elem),
+            None if state.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    // like recv, but gives up after `timeout`
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        // a timeout too large for an Instant (think Duration::MAX) is as good as none at all
+        let Some(deadline) = Instant::now().checked_add(timeout) else {
+            return self.recv().map_err(|RecvError| RecvTimeoutError::Disconnected);
+        };
+        let mut state = self.shared.lock();
+        loop {
+            if let Some(elem) = self.take(&mut state) {
+                return Ok(elem);
+            }
+            if state.senders == 0 {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            // wakeups can be spurious (or stolen by another receiver), so always wait for what's LEFT of the timeout
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            state = self.shared.not_empty.wait_timeout(state, deadline - now)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        }
+    }
+
+    // pops a message, and lets a waiting sender know there's room now
+    fn take(&self, state: &mut State<T>) -> Option<T> {
+        let elem = state.queue.pop()?;
+        state.len -= 1;
+        self.shared.not_full.notify_one();
+        Some(elem)
+    }
+
+    // blocks for every message, ends once all senders are gone (and everything has been received)
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { rx: self }
+    }
+
+    // only what's in the channel right now, without blocking
+    pub fn try_iter(&self) -> TryIter<'_, T> {
+        TryIter { rx: self }
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        self.shared.lock().receivers += 1;
+        Receiver { shared: self.shared.clone() }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.receivers -= 1;
+        if state.receivers == 0 {
+            // nobody will ever read these: drop them now, rather than whenever the last sender goes.
+            // Not while holding the lock though - a message may well contain a Sender of this very channel,
+            // whose Drop would then try to take the lock again, and deadlock.
+            let queue = mem::take(&mut state.queue);
+            state.len = 0;
+            drop(state);
+            // wake up every waiting sender, so they can notice
+            self.shared.not_full.notify_all();
+            drop(queue);
+        }
+    }
+}
+
+pub struct Iter<'a, T> {
+    rx: &'a Receiver<T>,
+}
+
+pub struct TryIter<'a, T> {
+    rx: &'a Receiver<T>,
+}
+
+pub struct IntoIter<T> {
+    rx: Receiver<T>,
+}
+
+impl<T> Iterator for Iter<'_, T> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        self.rx.recv().ok()
+    }
+}
+
+impl<T> Iterator for TryIter<'_, T> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        self.rx.try_recv().ok()
+    }
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        self.rx.recv().ok()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Receiver<T> {
+    type Item = T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<T> IntoIterator for Receiver<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter { rx: self }
+    }
+}
+
+
+// Errors
+
+// all receivers are gone; here's your message back
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub struct SendError<T>(pub T);
+
+// all senders are gone, and the channel is empty
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RecvError;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TryRecvError {
+    Empty,
+    Disconnected,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RecvTimeoutError {
+    Timeout,
+    Disconnected,
+}
+
+// no T: Debug needed to report that sending failed
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SendError { .. }")
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "sending on a channel without receivers")
+    }
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "receiving on an empty channel without senders")
+    }
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => write!(f, "receiving on an empty channel"),
+            TryRecvError::Disconnected => RecvError.fmt(f),
+        }
+    }
+}
+
+impl fmt::Display for RecvTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecvTimeoutError::Timeout => write!(f, "timed out waiting on the channel"),
+            RecvTimeoutError::Disconnected => RecvError.fmt(f),
+        }
+    }
+}
+
+impl<T> Error for SendError<T> {}
+impl Error for RecvError {}
+impl Error for TryRecvError {}
+impl Error for RecvTimeoutError {}
+
+
+#[cfg(test)]
+mod test {
+    use crate::fifth::channel::{channel, RecvError, RecvTimeoutError, SendError, TryRecvError};
+    use crate::fifth::channel::{Receiver, Sender};
+    use crate::test_util::{DropTracker, assert_send_sync};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn basics() {
+        let (tx, rx) = channel(4);
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        assert_eq!(rx.recv(), Ok(1));
+        tx.send(3).unwrap();
+        assert_eq!(rx.recv(), Ok(2));
+        assert_eq!(rx.try_recv(), Ok(3));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+    }
+
+    #[test]
+    #[should_panic(expected = "capacity must be at least 1")]
+    fn zero_capacity() {
+        let _ = channel::<i32>(0);
+    }
+
+    // spins until `counter` reaches `target`, however slow the other thread is today
+    fn wait_for(counter: &AtomicUsize, target: usize) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while counter.load(Ordering::SeqCst) < target {
+            assert!(Instant::now() < deadline, "stuck at {}, waiting for {}", counter.load(Ordering::SeqCst), target);
+            thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn backpressure() {
+        let (tx, rx) = channel(3);
+        let sent = AtomicUsize::new(0);
+        thread::scope(|s| {
+            s.spawn(|| {
+                for i in 0..10 {
+                    tx.send(i).unwrap();
+                    sent.fetch_add(1, Ordering::SeqCst);
+                }
+            });
+
+            // the sender fills the channel, then has to wait for us
+            wait_for(&sent, 3);
+            thread::sleep(Duration::from_millis(50));
+            assert_eq!(sent.load(Ordering::SeqCst), 3);
+            assert_eq!(rx.recv(), Ok(0));
+            wait_for(&sent, 4);
+            thread::sleep(Duration::from_millis(50));
+            assert_eq!(sent.load(Ordering::SeqCst), 4);
+
+            // and the order survives all the waiting
+            let rest: Vec<_> = (1..10).map(|_| rx.recv().unwrap()).collect();
+            assert_eq!(rest, (1..10).collect::<Vec<_>>());
+        });
+    }
+
+    #[test]
+    fn senders_disconnect() {
+        let (tx, rx) = channel(4);
+        let tx2 = tx.clone();
+        tx.send(1).unwrap();
+        drop(tx);
+        // one sender left: still connected
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+
+        tx2.send(2).unwrap();
+        drop(tx2);
+        // what was sent still arrives, then the channel reports the disconnect
+        assert_eq!(rx.recv(), Ok(2));
+        assert_eq!(rx.recv(), Err(RecvError));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+        assert_eq!(rx.recv_timeout(Duration::from_secs(10)), Err(RecvTimeoutError::Disconnected));
+    }
+
+    #[test]
+    fn disconnect_wakes_up_receiver() {
+        let (tx, rx) = channel::<i32>(1);
+        thread::scope(|s| {
+            let receiver = s.spawn(|| rx.recv());
+            thread::sleep(Duration::from_millis(50));
+            drop(tx);
+            assert_eq!(receiver.join().unwrap(), Err(RecvError));
+        });
+    }
+
+    #[test]
+    fn receivers_disconnect() {
+        let (tx, rx) = channel(1);
+        let rx2 = rx.clone();
+        drop(rx);
+        tx.send(1).unwrap();
+        drop(rx2);
+        // the message comes back
+        assert_eq!(tx.send(2), Err(SendError(2)));
+        assert_eq!(format!("{:?}", tx.send(3).unwrap_err()), "SendError { .. }");
+    }
+
+    #[test]
+    fn disconnect_wakes_up_sender() {
+        let (tx, rx) = channel(1);
+        tx.send(1).unwrap();
+        thread::scope(|s| {
+            // full channel: this one blocks
+            let sender = s.spawn(|| tx.send(2));
+            thread::sleep(Duration::from_millis(50));
+            drop(rx);
+            assert_eq!(sender.join().unwrap(), Err(SendError(2)));
+        });
+    }
+
+    #[test]
+    fn recv_timeout() {
+        let (tx, rx) = channel(1);
+        let start = Instant::now();
+        assert_eq!(rx.recv_timeout(Duration::from_millis(50)), Err(RecvTimeoutError::Timeout));
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        thread::scope(|s| {
+            s.spawn(|| {
+                thread::sleep(Duration::from_millis(50));
+                tx.send(1).unwrap();
+            });
+            assert_eq!(rx.recv_timeout(Duration::from_secs(10)), Ok(1));
+        });
+    }
+
+    #[test]
+    fn recv_timeout_forever() {
+        let (tx, rx) = channel(1);
+        tx.send(1).unwrap();
+        assert_eq!(rx.recv_timeout(Duration::MAX), Ok(1));
+        drop(tx);
+        assert_eq!(rx.recv_timeout(Duration::MAX), Err(RecvTimeoutError::Disconnected));
+    }
+
+    #[test]
+    fn iter() {
+        let (tx, rx) = channel(2);
+        thread::scope(|s| {
+            s.spawn(move || {
+                for i in 0..100 {
+                    tx.send(i).unwrap();
+                }
+                // dropping tx ends the iteration on the other side
+            });
+            assert_eq!(rx.iter().sum::<i32>(), 4950);
+        });
+
+        let (tx, rx) = channel(4);
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        // try_iter stops at the end of what's there, without waiting for more
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![1, 2]);
+        tx.send(3).unwrap();
+        drop(tx);
+        let mut collected = Vec::new();
+        for elem in rx {
+            collected.push(elem);
+        }
+        assert_eq!(collected, vec![3]);
+    }
+
+    #[test]
+    fn many_producers_many_consumers() {
+        const PRODUCERS: usize = 8;
+        const CONSUMERS: usize = 4;
+        const PER_PRODUCER: usize = 10_000;
+
+        let (tx, rx) = channel(16);
+        let received: Vec<AtomicUsize> = (0..PRODUCERS * PER_PRODUCER).map(|_| AtomicUsize::new(0)).collect();
+        thread::scope(|s| {
+            for p in 0..PRODUCERS {
+                let tx = tx.clone();
+                s.spawn(move || {
+                    for i in 0..PER_PRODUCER {
+                        tx.send(p * PER_PRODUCER + i).unwrap();
+                    }
+                });
+            }
+            // the consumers only stop once every sender is gone, ours included
+            drop(tx);
+            for _ in 0..CONSUMERS {
+                let (rx, received) = (rx.clone(), &received);
+                s.spawn(move || {
+                    for elem in rx {
+                        received[elem].fetch_add(1, Ordering::Relaxed);
+                    }
+                });
+            }
+        });
+        for (elem, count) in received.iter().enumerate() {
+            assert_eq!(count.load(Ordering::Relaxed), 1, "element {} received {} times", elem, count.load(Ordering::Relaxed));
+        }
+    }
+
+    #[test]
+    fn no_leaks() {
+        let tracker = DropTracker::new(4);
+        let (tx, rx) = channel(4);
+        for id in 0..4 {
+            tx.send(tracker.counter(id)).unwrap();
+        }
+        assert_eq!(rx.recv().map(|elem| elem.id()), Ok(0));
+        // the last receiver takes the unread messages down with it, senders or not
+        drop(rx);
+        tracker.assert_all_dropped_once();
+        drop(tx);
+    }
+
+    #[test]
+    fn message_holding_a_sender() {
+        // dropping the unread message drops a Sender, which locks the channel: must not deadlock
+        struct Message(Sender<Message>);
+
+        let (tx, rx) = channel(1);
+        tx.send(Message(tx.clone())).unwrap();
+        drop(rx);
+        let SendError(Message(returned)) = tx.send(Message(tx.clone())).unwrap_err();
+        assert!(returned.send(Message(tx.clone())).is_err());
+    }
+
+    #[test]
+    fn auto_traits() {
+        assert_send_sync::<Sender<i32>>();
+        assert_send_sync::<Receiver<i32>>();
+    }
+}
@@ -59,36 +59,55 @@ This works because B can never be all 0's, since it contains a non-zero pointer.
 use std::mem;
 
 /* Layout 4 */
-pub struct List {
+// generalized over the element type T; the hand-rolled Link enum stays,
+// so `List<T>` keeps the null-pointer-optimized layout (checked in the tests)
+
+/// A stack that owns its elements through `Box`es, so it behaves just like a `Box<T>` would:
+/// covariant over `T`, and `Send`/`Sync` exactly when `T` is.
+///
+/// ```compile_fail
+/// // Rc isn't Send, so a stack of Rc's can't be sent to another thread either
+/// use std::rc::Rc;
+/// use tutorial_too_many_lists::first::List;
+///
+/// let mut list = List::new();
+/// list.push(Rc::new(1));
+/// std::thread::spawn(move || drop(list));
+/// ```
+pub struct List<T> {
     // List is a struct with a single field
     // --> the size of List is the size of the field
     // --> Zero Cost Abstraction 
-    head: Link,
+    head: Link<T>,
 }
 
-enum Link {
+enum Link<T> {
     Empty,
-    More(Box<Node>),
+    More(Box<Node<T>>),
 }
 
-struct Node {
-    elem: i32, 
-    next: Link,
+struct Node<T> {
+    elem: T, 
+    // number of elements from this node to the end of the list (this one included)
+    // --> stored in the nodes rather than the List, so List stays a single pointer (see Layout 4)
+    len: usize,
+    next: Link<T>,
 }
 
 // 2.2
 // Constructor (for empty list)
-impl List {
+impl<T> List<T> {
     pub fn new() -> Self {
         List { head: Link::Empty }
     }
 
     // 2.3 
     // Push
-    pub fn push(&mut self, elem:i32) {
+    pub fn push(&mut self, elem: T) {
         let new_node = Box::new(
             Node {
-                elem: elem,
+                elem,
+                len: self.len() + 1,
                 next: mem::replace(&mut self.head, Link::Empty),
             }
         );
@@ -97,35 +116,67 @@ impl List {
 
     // 2.4
     // Pop
-    pub fn pop(&mut self) -> Option<i32> {
-        match mem::replace(&mut self.head, Link::Empty) {
+    pub fn pop(&mut self) -> Option<T> {
+        match self.pop_node() {
             Link::Empty => None,
-            Link::More(node) => {
-                self.head = node.next;
-                Some(node.elem)
+            Link::More(node) => Some(node.elem),
+        }
+    }
+
+    // a look at the top of the stack, without taking it off
+    pub fn peek(&self) -> Option<&T> {
+        match &self.head {
+            Link::Empty => None,
+            Link::More(node) => Some(&node.elem),
+        }
+    }
+
+    // every node knows how many elements follow it, so the head node knows the length of the whole list
+    pub fn len(&self) -> usize {
+        match &self.head {
+            Link::Empty => 0,
+            Link::More(node) => node.len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        matches!(self.head, Link::Empty)
+    }
+
+    // unlinks the head node, but keeps it in its Box
+    // --> only pointers get moved around, the element stays put on the heap
+    fn pop_node(&mut self) -> Link<T> {
+        match mem::replace(&mut self.head, Link::Empty) {
+            Link::Empty => Link::Empty,
+            Link::More(mut boxed_node) => {
+                // detach the rest of the list from the node before handing the node out
+                self.head = mem::replace(&mut boxed_node.next, Link::Empty);
+                Link::More(boxed_node)
             }
         }
     }
 }
 
-impl Drop for List {
+impl<T> Default for List<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for List<T> {
     fn drop(&mut self) {
-        // pull the current head link from its Box, replace it with an empty value
-        let mut cur_link = mem::replace(&mut self.head, Link::Empty);
         // `while let` == "do this thing until this pattern doesn't match" -> while not empty
-        while let Link::More(mut boxed_node) = cur_link { // move the value, NOT a reference
-            cur_link = mem::replace(&mut boxed_node.next, Link::Empty);
-            // boxed_node goes out of scope at the end of every step in the loop
-            // --> implicitely gets dropped
-            // its Node's `next` field is set to Link::Empty, so no unbound recursion occurs 
-            // --> iterates over full list, moving non-trivial structures out to temp variables and implicitely dropping the rest
+        while let Link::More(_boxed_node) = self.pop_node() {
+            // _boxed_node goes out of scope at the end of every step in the loop
+            // --> implicitely gets dropped, element included, right where it lives in its Box
+            // its Node's `next` field was set to Link::Empty by pop_node, so no unbound recursion occurs 
         }
     }
     /* 
         Alternate idea: use 
             while let Some(_) = self.pop() { }
         Difference:
-            Pop returns Option<i32>, while the above implementation only manipulates Links (i.e. Box<Node>)
+            Pop returns Option<T>, while pop_node only manipulates Links (i.e. Box<Node>)
             -> only moves around pointers, while pop moves values
         Problem:
             Moving values can become very expensive in a generalized list, where values can become big instances of VeryBigThingWithADropImpl (VBTWADI).
@@ -133,7 +184,7 @@ impl Drop for List {
             Since VBTWADI is exactly what makes using linked-lists desirable over arrays in the first place, this bad performance would let the entire concept down.
         Solution:
             Best-of-both-worlds implementation: 
-            Add new method `fn pop_node(&mut self) -> Link`, from which both `pop` and `drop` can be cleanly derived.
+            `fn pop_node(&mut self) -> Link<T>`, from which both `pop` and `drop` are cleanly derived (see above).
 
      */
 }
@@ -169,4 +220,100 @@ mod test {
         // check list exhaustiong
         assert_eq!(list.pop(), None);
     }
+
+    #[test]
+    fn peek() {
+        let mut list = List::new();
+        assert_eq!(list.peek(), None);
+
+        list.push(1); list.push(2);
+        assert_eq!(list.peek(), Some(&2));
+        assert_eq!(list.pop(), Some(2));
+        assert_eq!(list.peek(), Some(&1));
+        assert_eq!(list.pop(), Some(1));
+        assert_eq!(list.peek(), None);
+    }
+
+    #[test]
+    fn len() {
+        let mut list = List::new();
+        assert_eq!(list.len(), 0);
+        assert!(list.is_empty());
+
+        list.push(1); list.push(2); list.push(3);
+        assert_eq!(list.len(), 3);
+        assert!(!list.is_empty());
+
+        list.pop();
+        assert_eq!(list.len(), 2);
+        list.push(4);
+        assert_eq!(list.len(), 3);
+
+        list.pop(); list.pop(); list.pop();
+        assert_eq!(list.len(), 0);
+        assert!(list.is_empty());
+        list.pop();
+        assert_eq!(list.len(), 0);
+    }
+
+    #[test]
+    fn generic() {
+        let mut list = List::new();
+        list.push(String::from("hello"));
+        list.push(String::from("world"));
+
+        assert_eq!(list.pop(), Some(String::from("world")));
+        assert_eq!(list.pop(), Some(String::from("hello")));
+        assert_eq!(list.pop(), None);
+    }
+
+    #[test]
+    fn null_pointer_optimization() {
+        use std::mem::size_of;
+
+        // the Link enum needs no tag, so the whole List is just one pointer
+        assert_eq!(size_of::<List<i32>>(), size_of::<usize>());
+        assert_eq!(size_of::<List<String>>(), size_of::<usize>());
+        assert_eq!(size_of::<List<()>>(), size_of::<usize>());
+    }
+
+    #[test]
+    fn variance_and_auto_traits() {
+        use crate::test_util::{assert_covariant, assert_send_sync};
+
+        assert_covariant!(List<T>);
+        assert_send_sync::<List<i32>>();
+    }
+
+    #[test]
+    fn long_drop() {
+        crate::test_util::drop_on_small_stack(|| {
+            let mut list = List::new();
+            for elem in 0..crate::test_util::LONG {
+                list.push(elem);
+            }
+            list
+        });
+    }
+
+    #[test]
+    fn no_leaks() {
+        use crate::test_util::{assert_no_leaks, DropTracker};
+
+        let tracker = DropTracker::new(6);
+        assert_no_leaks(|| {
+            let mut list = List::new();
+            list.push(tracker.counter(0));
+            list.push(tracker.counter(1));
+            list.push(tracker.counter(2));
+            assert_eq!(list.pop().map(|elem| elem.id()), Some(2));
+            list.push(tracker.counter(3));
+            assert_eq!(tracker.drops(2), 1);
+            list.pop();
+            // the rest goes down with the list
+            list.push(tracker.counter(4));
+            list.push(tracker.counter(5));
+        });
+        tracker.assert_all_dropped_once();
+    }
 }
\ No newline at end of file
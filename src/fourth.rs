@@ -17,20 +17,54 @@
     Also, the list itself has a pointer to the first and last node. This gives us fast insertion and removal on both ends of the list.
  */
 
-use std::rc::Rc;
-use std::cell::{RefCell, Ref, RefMut};
+use std::rc::{Rc, Weak};
+use std::cell::{BorrowError, BorrowMutError, RefCell, Ref, RefMut};
+use std::error::Error;
+use std::fmt;
 
+/// Neither `Send` nor `Sync` (the nodes are `Rc`s), and INVARIANT over `T`: 
+/// every element sits inside a `RefCell`, and anything that can mutate through a shared reference has to be invariant -
+/// otherwise a `&List<&'static str>` could be used to store a short-lived `&str` in it.
+/// The iterators and the cursor point into those `RefCell`s too, so the same goes for them.
+/// Not "fixable" without giving up on `RefCell` - which is exactly what Chapter 6 and 7 do.
+///
+/// ```compile_fail
+/// use tutorial_too_many_lists::fourth::List;
+///
+/// fn shorten<'a>(list: List<&'static str>) -> List<&'a str> {
+///     list
+/// }
+/// ```
+///
+/// ```compile_fail
+/// use tutorial_too_many_lists::fourth::List;
+///
+/// let mut list = List::new();
+/// list.push_front(1);
+/// std::thread::spawn(move || drop(list));
+/// ```
+///
+/// ```compile_fail
+/// use tutorial_too_many_lists::fourth::List;
+///
+/// let list: List<i32> = List::new();
+/// std::thread::scope(|s| { s.spawn(|| list.len()); });
+/// ```
 pub struct List<T> {
     head: Link<T>,
     tail: Link<T>,
+    // number of nodes, kept up to date by the push/pop methods
+    len: usize,
 }
 
 type Link<T> = Option<Rc<RefCell<Node<T>>>>;
+// back-pointers don't own anything (see Chapter 5.10)
+type WeakLink<T> = Option<Weak<RefCell<Node<T>>>>;
 
 struct Node<T> {
     elem: T, 
     next: Link<T>,
-    prev: Link<T>,
+    prev: WeakLink<T>,
 }
 
 // Chapter 5.2 : Building Up
@@ -39,7 +73,7 @@ impl <T> Node<T> {
     fn new(elem: T) -> Rc<RefCell<Self>> {
         Rc::new(RefCell::new(
             Node {
-                elem: elem, 
+                elem, 
                 prev: None,
                 next: None,
             }
@@ -47,9 +81,14 @@ impl <T> Node<T> {
     }
 }
 
+// follows a back-pointer. As long as a node is in the list, its predecessor owns it, so this never fails
+fn upgrade<T>(prev: Weak<RefCell<Node<T>>>) -> Rc<RefCell<Node<T>>> {
+    prev.upgrade().expect("the predecessor of a node in the list is alive")
+}
+
 impl<T> List<T> {
     pub fn new() -> Self {
-        List { head: None, tail: None }
+        List { head: None, tail: None, len: 0 }
     }
 
     /* 
@@ -68,7 +107,7 @@ impl<T> List<T> {
         match self.head.take() {
             Some(old_head) => {
                 // non-empty list -> needs to connect to old_head
-                old_head.borrow_mut().prev = Some(new_head.clone()); // +1 new_head
+                old_head.borrow_mut().prev = Some(Rc::downgrade(&new_head)); // +1 new_head (weak)
                 new_head.borrow_mut().next = Some(old_head);         // +1 old_head
                 self.head = Some(new_head);             // +1 new_head, -1 old_head
                 // total: +2 new_head, +0 old_head -- OK!
@@ -80,18 +119,30 @@ impl<T> List<T> {
                 // total: +2 new_head -- OK!
             }
         }
+        self.len += 1;
     }
 
     // Chapter 5.3 : Breaking Down
 
     // pop_front has same basic logic as push_front, but backward
+    /// Can't fail: every guard and cursor borrows the list, so while we hold `&mut self` nothing else
+    /// can borrow a node, and the only owners of the head node are the list's own links.
+    /// `try_pop_front` (Chapter 5.11) is the same thing, returning a `PopError` instead of panicking if that ever breaks.
     pub fn pop_front(&mut self) -> Option<T> {
+        self.try_pop_front().unwrap_or_else(|err| unreachable!("pop_front: {}", err))
+    }
+
+    pub fn try_pop_front(&mut self) -> Result<Option<T>, PopError> {
+        // make sure we will actually get the element out, BEFORE touching any links (Chapter 5.11)
+        if let Some(head) = &self.head {
+            check_poppable(head, if self.len == 1 { 2 } else { 1 })?;
+        }
         // needs to take the old head and ensure it's -2
-        self.head.take().map(|old_head|  {                  // -1 old (happing in any case)
+        Ok(self.head.take().map(|old_head|  {              // -1 old (happing in any case)
             match old_head.borrow_mut().next.take() {
                 Some(new_head) => {                         // -1 new (only if exists)
                     // when not emptying list
-                    new_head.borrow_mut().prev.take();      // -1 old
+                    new_head.borrow_mut().prev.take();      // -1 old (weak)
                     self.head = Some(new_head);             // +1 new
                 }
                 None => {
@@ -100,20 +151,29 @@ impl<T> List<T> {
                     // total: -2 old, (no new)
                 }
             }
-            Rc::try_unwrap(old_head).ok().unwrap().into_inner().elem
-        })
+            self.len -= 1;
+            Rc::try_unwrap(old_head).ok().expect("checked by check_poppable").into_inner().elem
+        }))
     }
 
 
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
     // Chapter 5.4 : Peeking
 
-    pub fn peek_front(& self) -> Option<Ref<T>> {
+    pub fn peek_front(& self) -> Option<Ref<'_, T>> {
         self.head.as_ref().map(|node| {
             Ref::map(node.borrow(), |node| &node.elem)
         })
     }
 
-    pub fn peek_front_mut(&mut self) -> Option<RefMut<T>> {
+    pub fn peek_front_mut(&mut self) -> Option<RefMut<'_, T>> {
         self.head.as_ref().map(|node| {
             RefMut::map(node.borrow_mut(), |node| &mut node.elem)
         })
@@ -133,7 +193,7 @@ impl<T> List<T> {
             Some(old_tail) => {
                 // non-empty list -> needs to connect to old_head
                 old_tail.borrow_mut().next = Some(new_tail.clone()); // +1 new_head
-                new_tail.borrow_mut().prev = Some(old_tail);         // +1 old_head
+                new_tail.borrow_mut().prev = Some(Rc::downgrade(&old_tail)); // +1 old_head (weak)
                 self.tail = Some(new_tail);             // +1 new_head, -1 old_head
                 // total: +2 new_head, +0 old_head -- OK!
             }
@@ -144,14 +204,25 @@ impl<T> List<T> {
                 // total: +2 new_head -- OK!
             }
         }
+        self.len += 1;
     }
 
     // pop_front has same basic logic as push_front, but backward
+    /// Can't fail, for the same reasons as `pop_front`; `try_pop_back` returns the `PopError` instead.
     pub fn pop_back(&mut self) -> Option<T> {
+        self.try_pop_back().unwrap_or_else(|err| unreachable!("pop_back: {}", err))
+    }
+
+    pub fn try_pop_back(&mut self) -> Result<Option<T>, PopError> {
+        // the tail node is always owned by the tail AND its predecessor (or head)
+        if let Some(tail) = &self.tail {
+            check_poppable(tail, 2)?;
+        }
         // needs to take the old head and ensure it's -2
-        self.tail.take().map(|old_tail|  {                  // -1 old (happing in any case)
-            match old_tail.borrow_mut().prev.take() {
-                Some(new_tail) => {                         // -1 new (only if exists)
+        Ok(self.tail.take().map(|old_tail|  {              // -1 old (happing in any case)
+            // the back-pointer is weak, so we upgrade it to get at the node; the node itself is still owned by its predecessor
+            match old_tail.borrow_mut().prev.take().map(upgrade) {
+                Some(new_tail) => {                         // -1 new (weak, only if exists)
                     // when not emptying list
                     new_tail.borrow_mut().next.take();      // -1 old
                     self.tail = Some(new_tail);             // +1 new
@@ -162,17 +233,18 @@ impl<T> List<T> {
                     // total: -2 old, (no new)
                 }
             }
-            Rc::try_unwrap(old_tail).ok().unwrap().into_inner().elem
-        })
+            self.len -= 1;
+            Rc::try_unwrap(old_tail).ok().expect("checked by check_poppable").into_inner().elem
+        }))
     }
 
-    pub fn peek_back(& self) -> Option<Ref<T>> {
+    pub fn peek_back(& self) -> Option<Ref<'_, T>> {
         self.tail.as_ref().map(|node| {
             Ref::map(node.borrow(), |node| &node.elem)
         })
     }
 
-    pub fn peek_back_mut(&mut self) -> Option<RefMut<T>> {
+    pub fn peek_back_mut(&mut self) -> Option<RefMut<'_, T>> {
         self.tail.as_ref().map(|node| {
             RefMut::map(node.borrow_mut(), |node| &mut node.elem)
         })
@@ -182,19 +254,58 @@ impl<T> List<T> {
 }
 
 
+impl<T> Default for List<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T> Drop for List<T> {
     fn drop(&mut self) {
-        // pop until None, do nothing with it -> let Nodes & Links just go out of scope
-        while self.pop_front().is_some() {}
+        // no popping here: we don't need the elements, only the links cut, one node at a time.
+        // Taking `next` out of each node before letting go of it means no drop ever recurses down the list -
+        // and a node that is still owned elsewhere (see Chapter 5.11) just stays alive on its own, instead of stopping us.
+        self.tail.take();
+        let mut next = self.head.take();
+        while let Some(node) = next {
+            next = node.borrow_mut().next.take();
+        }
     }
 }
 
 // Chapter 5.6 : Iteration
 
 // IntoIter
+
+/// Invariant over `T`, and neither `Send` nor `Sync`: it's just the list (see `List`).
+///
+/// ```compile_fail
+/// use tutorial_too_many_lists::fourth::IntoIter;
+///
+/// fn shorten<'a>(iter: IntoIter<&'static str>) -> IntoIter<&'a str> {
+///     iter
+/// }
+/// ```
+///
+/// ```compile_fail
+/// use tutorial_too_many_lists::fourth::List;
+///
+/// fn assert_send<T: Send>(_: &T) {}
+/// let iter = List::<i32>::new().into_iter();
+/// assert_send(&iter);
+/// ```
+///
+/// ```compile_fail
+/// use tutorial_too_many_lists::fourth::List;
+///
+/// fn assert_sync<T: Sync>(_: &T) {}
+/// let iter = List::<i32>::new().into_iter();
+/// assert_sync(&iter);
+/// ```
 pub struct IntoIter<T>(List<T>);
 
 impl<T> List<T> {
+    #[allow(clippy::should_implement_trait)]
     pub fn into_iter(self) -> IntoIter<T> {
         IntoIter(self)
     }
@@ -256,9 +367,644 @@ impl<'a, T> Iterator for Iter<'a, T> {
 } 
  */
 
+// Chapter 5.7 : Iter and IterMut, revisited
+/* 
+    Giving up is no fun, though. Let's take a second look at WHY the attempt above failed:
+    every Ref we get from a node only lives as long as the borrow of the node we got it from, 
+    so we can never get a Ref<'a, _> for the NEXT node out of it - even though that node clearly lives long enough.
+
+    But look at what the iterators borrow: `&'a List<T>` (or `&'a mut List<T>`).
+    While that borrow is alive, nobody can push or pop, and the links between the nodes can only be changed 
+    through the List (the nodes never escape this module). So for all of 'a:
+        - every node stays alive, because the list (through its predecessor) keeps owning it
+        - every `next`/`prev` link stays exactly what it is right now
+    
+    That is an invariant the compiler can't see, so we spell it out ourselves with a tiny bit of unsafe:
+    we peek at a node's link through a short-lived borrow, and then stretch the reference to the neighbouring 
+    RefCell to 'a. The elements themselves are still handed out as Ref/RefMut guards, so the RefCell keeps doing its job.
+
+    Both iterators walk from both ends at once, and `len` tells them when the two ends have met.
+ */
+
+impl<T> Node<T> {
+    // SAFETY: the caller must guarantee that the links of the list `cell` belongs to can not change while `cell` is borrowed
+    // (i.e. the List is borrowed at least that long), and that `cell` is not mutably borrowed right now
+    unsafe fn next_cell(cell: &RefCell<Self>) -> Option<&RefCell<Self>> {
+        let next = cell.borrow().next.as_deref().map(|next| next as *const RefCell<Self>);
+        next.map(|next| unsafe { &*next })
+    }
+
+    // SAFETY: same as next_cell
+    unsafe fn prev_cell(cell: &RefCell<Self>) -> Option<&RefCell<Self>> {
+        let prev = cell.borrow().prev.as_ref().map(Weak::as_ptr);
+        prev.map(|prev| unsafe { &*prev })
+    }
+}
+
+// Iter
+
+/// Invariant over `T`, like the list itself (see `List`), even though it only hands out shared `Ref`s.
+/// It borrows `RefCell`s, which aren't `Sync`, so it can't be `Send` or `Sync` either.
+///
+/// ```compile_fail
+/// use tutorial_too_many_lists::fourth::Iter;
+///
+/// fn shorten<'i, 'a>(iter: Iter<'i, &'static str>) -> Iter<'i, &'a str> {
+///     iter
+/// }
+/// ```
+///
+/// ```compile_fail
+/// use tutorial_too_many_lists::fourth::List;
+///
+/// fn assert_send<T: Send>(_: &T) {}
+/// let list = List::<i32>::new();
+/// let iter = list.iter();
+/// assert_send(&iter);
+/// ```
+///
+/// ```compile_fail
+/// use tutorial_too_many_lists::fourth::List;
+///
+/// fn assert_sync<T: Sync>(_: &T) {}
+/// let list = List::<i32>::new();
+/// let iter = list.iter();
+/// assert_sync(&iter);
+/// ```
+pub struct Iter<'a, T> {
+    front: Option<&'a RefCell<Node<T>>>,
+    back: Option<&'a RefCell<Node<T>>>,
+    // elements left between front and back
+    len: usize,
+}
+
+impl<T> List<T> {
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { front: self.head.as_deref(), back: self.tail.as_deref(), len: self.len }
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = Ref<'a, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.front.take().map(|cell| {
+            // SAFETY: we hold a shared borrow of the List for 'a, and shared iteration never borrows mutably
+            self.front = unsafe { Node::next_cell(cell) };
+            self.len -= 1;
+            Ref::map(cell.borrow(), |node| &node.elem)
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> DoubleEndedIterator for Iter<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.back.take().map(|cell| {
+            // SAFETY: see next
+            self.back = unsafe { Node::prev_cell(cell) };
+            self.len -= 1;
+            Ref::map(cell.borrow(), |node| &node.elem)
+        })
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+// IterMut
+
+/// Same as `Iter`: invariant over `T`, neither `Send` nor `Sync`.
+///
+/// ```compile_fail
+/// use tutorial_too_many_lists::fourth::IterMut;
+///
+/// fn shorten<'i, 'a>(iter: IterMut<'i, &'static str>) -> IterMut<'i, &'a str> {
+///     iter
+/// }
+/// ```
+///
+/// ```compile_fail
+/// use tutorial_too_many_lists::fourth::List;
+///
+/// fn assert_send<T: Send>(_: &T) {}
+/// let mut list = List::<i32>::new();
+/// let iter = list.iter_mut();
+/// assert_send(&iter);
+/// ```
+///
+/// ```compile_fail
+/// use tutorial_too_many_lists::fourth::List;
+///
+/// fn assert_sync<T: Sync>(_: &T) {}
+/// let mut list = List::<i32>::new();
+/// let iter = list.iter_mut();
+/// assert_sync(&iter);
+/// ```
+pub struct IterMut<'a, T> {
+    front: Option<&'a RefCell<Node<T>>>,
+    back: Option<&'a RefCell<Node<T>>>,
+    len: usize,
+}
+
+impl<T> List<T> {
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut { front: self.head.as_deref(), back: self.tail.as_deref(), len: self.len }
+    }
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = RefMut<'a, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.front.take().map(|cell| {
+            // SAFETY: we hold the List mutably borrowed for 'a; `cell` has not been yielded yet, 
+            // so nobody holds a RefMut to it, and we read its link BEFORE handing out the guard
+            self.front = unsafe { Node::next_cell(cell) };
+            self.len -= 1;
+            RefMut::map(cell.borrow_mut(), |node| &mut node.elem)
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> DoubleEndedIterator for IterMut<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.back.take().map(|cell| {
+            // SAFETY: see next
+            self.back = unsafe { Node::prev_cell(cell) };
+            self.len -= 1;
+            RefMut::map(cell.borrow_mut(), |node| &mut node.elem)
+        })
+    }
+}
+
+impl<T> ExactSizeIterator for IterMut<'_, T> {}
+
+
+// Chapter 5.8 : Cursors - editing in the middle
+/* 
+    The whole point of a doubly-linked list is cheap editing ANYWHERE, not just at the ends.
+    So let's steal the design of std's `linked_list::CursorMut`: 
+    a cursor points at one element of the list, and can walk forwards and backwards from there.
+
+    Just like in std, the cursor can also point at a "ghost" non-element that sits between tail and head.
+    Walking off either end lands on the ghost, and walking on from the ghost wraps around to the other end.
+    This saves us from a lot of "but what if the list is empty" special casing in the API.
+
+    The pointer invariant still holds for every node - every node has exactly two pointers to it from the list.
+    The cursor itself holds one extra pointer to the current node while it sits on it; 
+    that pointer is just on loan, and is always given back before a node gets unwrapped.
+
+    Splicing and inserting are the same operation: insert_before/insert_after just splice in a list of one element.
+ */
+
+/// Invariant over `T` (it holds a `&mut List<T>`), and neither `Send` nor `Sync` (it holds an `Rc` to the current node).
+///
+/// ```compile_fail
+/// use tutorial_too_many_lists::fourth::CursorMut;
+///
+/// fn shorten<'c, 'a>(cursor: CursorMut<'c, &'static str>) -> CursorMut<'c, &'a str> {
+///     cursor
+/// }
+/// ```
+///
+/// ```compile_fail
+/// use tutorial_too_many_lists::fourth::List;
+///
+/// fn assert_send<T: Send>(_: &T) {}
+/// let mut list = List::<i32>::new();
+/// let cursor = list.cursor_front_mut();
+/// assert_send(&cursor);
+/// ```
+///
+/// ```compile_fail
+/// use tutorial_too_many_lists::fourth::List;
+///
+/// fn assert_sync<T: Sync>(_: &T) {}
+/// let mut list = List::<i32>::new();
+/// let cursor = list.cursor_front_mut();
+/// assert_sync(&cursor);
+/// ```
+pub struct CursorMut<'a, T> {
+    list: &'a mut List<T>,
+    // None == the ghost non-element
+    current: Link<T>,
+    index: Option<usize>,
+}
+
+impl<T> List<T> {
+    // cursor starting at the front element (or at the ghost, if the list is empty)
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, T> {
+        let current = self.head.clone();
+        let index = current.as_ref().map(|_| 0);
+        CursorMut { list: self, current, index }
+    }
+
+    // cursor starting at the back element (or at the ghost, if the list is empty)
+    pub fn cursor_back_mut(&mut self) -> CursorMut<'_, T> {
+        let current = self.tail.clone();
+        let index = current.as_ref().map(|_| self.len - 1);
+        CursorMut { list: self, current, index }
+    }
+
+    // links all nodes of `other` in between `prev` and `next`, which must be neighbours in self
+    // (None meaning "the respective end of the list")
+    fn splice_between(&mut self, prev: Link<T>, next: Link<T>, mut other: List<T>) {
+        let (other_head, other_tail) = match (other.head.take(), other.tail.take()) {
+            (Some(head), Some(tail)) => (head, tail),
+            _ => return,
+        };
+        self.len += std::mem::replace(&mut other.len, 0);
+
+        // prev -> other_head:  +1 other_head, -1 next
+        match &prev {
+            Some(prev) => prev.borrow_mut().next = Some(other_head.clone()),
+            None => self.head = Some(other_head.clone()),
+        }
+        // other_tail <- next:  +1 other_tail, -1 prev
+        match &next {
+            Some(next) => next.borrow_mut().prev = Some(Rc::downgrade(&other_tail)),
+            None => self.tail = Some(other_tail.clone()),
+        }
+        // point at the neighbours:  +1 prev (weak), +1 next
+        other_head.borrow_mut().prev = prev.as_ref().map(Rc::downgrade);
+        other_tail.borrow_mut().next = next;
+        // total: +0 everywhere; other's head/tail pointers go out of scope here, making up for the clones above
+    }
+}
+
+impl<T> CursorMut<'_, T> {
+    // position of the current element, None for the ghost
+    pub fn index(&self) -> Option<usize> {
+        self.index
+    }
+
+    pub fn move_next(&mut self) {
+        match self.current.take() {
+            Some(current) => {
+                self.current = current.borrow().next.clone();
+                self.index = match self.current {
+                    Some(_) => self.index.map(|index| index + 1),
+                    // walked off the back -> ghost
+                    None => None,
+                };
+            }
+            None => {
+                // ghost -> wrap around to the front
+                self.current = self.list.head.clone();
+                self.index = self.current.as_ref().map(|_| 0);
+            }
+        }
+    }
+
+    pub fn move_prev(&mut self) {
+        match self.current.take() {
+            Some(current) => {
+                self.current = current.borrow().prev.clone().map(upgrade);
+                self.index = match self.current {
+                    Some(_) => self.index.map(|index| index - 1),
+                    // walked off the front -> ghost
+                    None => None,
+                };
+            }
+            None => {
+                // ghost -> wrap around to the back
+                self.current = self.list.tail.clone();
+                self.index = self.current.as_ref().map(|_| self.list.len - 1);
+            }
+        }
+    }
+
+    pub fn current(&mut self) -> Option<RefMut<'_, T>> {
+        self.current.as_ref().map(|node| {
+            RefMut::map(node.borrow_mut(), |node| &mut node.elem)
+        })
+    }
+
+    // inserts a new element right before the current one (at the back, when on the ghost)
+    pub fn insert_before(&mut self, elem: T) {
+        let mut other = List::new();
+        other.push_back(elem);
+        self.splice_before(other);
+    }
+
+    // inserts a new element right after the current one (at the front, when on the ghost)
+    pub fn insert_after(&mut self, elem: T) {
+        let mut other = List::new();
+        other.push_back(elem);
+        self.splice_after(other);
+    }
+
+    // moves all of `other` in right before the current element (at the back, when on the ghost)
+    pub fn splice_before(&mut self, other: List<T>) {
+        let other_len = other.len;
+        let (prev, next) = match &self.current {
+            Some(current) => (current.borrow().prev.clone().map(upgrade), Some(current.clone())),
+            None => (self.list.tail.clone(), None),
+        };
+        self.list.splice_between(prev, next, other);
+        // everything in front of us moved back
+        self.index = self.index.map(|index| index + other_len);
+    }
+
+    // moves all of `other` in right after the current element (at the front, when on the ghost)
+    pub fn splice_after(&mut self, other: List<T>) {
+        let (prev, next) = match &self.current {
+            Some(current) => (Some(current.clone()), current.borrow().next.clone()),
+            None => (None, self.list.head.clone()),
+        };
+        self.list.splice_between(prev, next, other);
+    }
+
+    // removes the current element and moves on to the next one (ghost stays ghost)
+    /// Can't fail, unlike `List::try_pop_front`: the cursor borrows the list mutably, so no guard or other cursor
+    /// can be alive, and once both neighbours (or head/tail) are re-linked, `current` is the node's only owner.
+    pub fn remove_current(&mut self) -> Option<T> {
+        // give back our loaned pointer first
+        let node = self.current.take()?;
+        let prev = node.borrow_mut().prev.take().map(upgrade);   // -1 prev
+        let next = node.borrow_mut().next.take();           // -1 next
+
+        // neighbours now point at each other instead of node: -2 node, +1 prev, +1 next
+        match &prev {
+            Some(prev) => prev.borrow_mut().next = next.clone(),
+            None => self.list.head = next.clone(),
+        }
+        match &next {
+            Some(next) => next.borrow_mut().prev = prev.as_ref().map(Rc::downgrade),
+            None => self.list.tail = prev.clone(),
+        }
+        self.list.len -= 1;
+
+        // the next element slides into our index; if there is none, we are on the ghost now
+        if next.is_none() {
+            self.index = None;
+        }
+        self.current = next;
+
+        Some(Rc::try_unwrap(node).ok().expect("the cursor held the last owner of the node").into_inner().elem)
+    }
+
+    // splits off everything after the current element into a new list (the whole list, when on the ghost)
+    pub fn split_after(&mut self) -> List<T> {
+        let (current, index) = match (&self.current, self.index) {
+            (Some(current), Some(index)) => (current, index),
+            _ => return std::mem::take(self.list),
+        };
+        match current.borrow_mut().next.take() {                // -1 next
+            Some(next) => {
+                next.borrow_mut().prev.take();                  // -1 current
+                let tail = self.list.tail.replace(current.clone());  // +1 current
+                let len = self.list.len - index - 1;
+                self.list.len = index + 1;
+                List { head: Some(next), tail, len }            // +1 next
+            }
+            None => List::new(),
+        }
+    }
+
+    // splits off everything before the current element into a new list (the whole list, when on the ghost)
+    pub fn split_before(&mut self) -> List<T> {
+        let (current, index) = match (&self.current, self.index) {
+            (Some(current), Some(index)) => (current, index),
+            _ => return std::mem::take(self.list),
+        };
+        match current.borrow_mut().prev.take().map(upgrade) {   // -1 prev
+            Some(prev) => {
+                prev.borrow_mut().next.take();                  // -1 current
+                let head = self.list.head.replace(current.clone());  // +1 current
+                self.list.len -= index;
+                self.index = Some(0);
+                List { head, tail: Some(prev), len: index }     // +1 prev
+            }
+            None => List::new(),
+        }
+    }
+}
+
+
+// Chapter 5.9 : Appending and splitting whole lists
+/* 
+    With head AND tail at hand, gluing two lists together is just re-wiring the two nodes at the seam.
+    splice_between already does exactly that, so append/prepend_list are one-liners on top of it.
+    The other list is left empty behind, just like with std's LinkedList::append.
+
+    split_off has to find the split point first, so it is O(min(at, len - at)) - 
+    we walk a cursor in from whichever end is closer, and let split_after do the re-wiring.
+ */
+
+impl<T> List<T> {
+    // moves all elements of `other` to the back of self, in O(1)
+    pub fn append(&mut self, other: &mut List<T>) {
+        let prev = self.tail.clone();
+        self.splice_between(prev, None, std::mem::take(other));
+    }
+
+    // moves all elements of `other` to the front of self, in O(1)
+    pub fn prepend_list(&mut self, other: &mut List<T>) {
+        let next = self.head.clone();
+        self.splice_between(None, next, std::mem::take(other));
+    }
+
+    // splits the list in two at the given index; self keeps [0, at), the returned list gets [at, len)
+    // panics if at > len
+    pub fn split_off(&mut self, at: usize) -> List<T> {
+        assert!(at <= self.len, "cannot split off at a nonexistent index");
+        if at == 0 {
+            return std::mem::take(self);
+        }
+
+        // put a cursor on the last element that stays with self
+        let mut cursor = if at - 1 < self.len / 2 {
+            let mut cursor = self.cursor_front_mut();
+            for _ in 0..at - 1 {
+                cursor.move_next();
+            }
+            cursor
+        } else {
+            let steps = self.len - at;
+            let mut cursor = self.cursor_back_mut();
+            for _ in 0..steps {
+                cursor.move_prev();
+            }
+            cursor
+        };
+        cursor.split_after()
+    }
+}
+
+
+// Chapter 5.10 : Weak back-pointers
+/* 
+    So far, two neighbours pointed at each other with two strong Rc's: a reference cycle. 
+    Rc can't collect cycles, so a single forgotten `take()` of a prev link leaked the whole rest of the deque, silently.
+
+    `Weak` is Rc's non-owning sibling: it points at the same allocation, but doesn't keep it alive.
+    To use it, `upgrade()` it into a proper Rc - which fails if the node is gone already.
+    So now ownership only flows forward: head -> next -> next -> ... -> last node, and the tail is a second owner of the last one.
+    The prev links are Weak, and can never keep anything alive on their own. No cycles, no leaks.
+
+    The old invariant still holds, we just know more about the kind of pointers now:
+        - every node has exactly two pointers to it
+        - a node with a successor: one strong (from its predecessor, or head) + one weak (its successor's prev)
+        - the last node: two strong (from its predecessor or head, and from tail), no weak
+    
+    `check_invariants` walks the list and verifies exactly that (plus len, and that prev/next agree).
+    It is meant for tests and debugging; in release builds it does nothing.
+ */
+
+impl<T> List<T> {
+    pub fn check_invariants(&self) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+        let mut len = 0;
+        let mut prev: Link<T> = None;
+        let mut cur = self.head.clone();
+        while let Some(node) = cur {
+            len += 1;
+            let is_last = node.borrow().next.is_none();
+            // minus the strong pointer `node` itself is holding
+            let strong = Rc::strong_count(&node) - 1;
+            assert_eq!(strong, if is_last { 2 } else { 1 }, "node {} has {} strong pointers", len - 1, strong);
+            assert_eq!(Rc::weak_count(&node), if is_last { 0 } else { 1 }, "node {} has wrong weak pointers", len - 1);
+            match (&prev, node.borrow().prev.as_ref().map(Weak::as_ptr)) {
+                (Some(expected), Some(actual)) => assert!(std::ptr::eq(Rc::as_ptr(expected), actual), "prev link broken"),
+                (None, None) => {}
+                _ => panic!("prev link broken"),
+            }
+            cur = node.borrow().next.clone();
+            prev = Some(node);
+        }
+        match (&prev, &self.tail) {
+            (Some(last), Some(tail)) => assert!(Rc::ptr_eq(last, tail), "tail link broken"),
+            (None, None) => {}
+            _ => panic!("tail link broken"),
+        }
+        assert_eq!(len, self.len, "len is off");
+    }
+}
+
+
+// Chapter 5.11 : Fallible peeking and popping
+/* 
+    RefCell moves the borrow checking to runtime, and runtime borrow errors are panics. Same goes for our
+    `Rc::try_unwrap(..).ok().unwrap()` in the pops: if anyone else still owns the node, we can't move the element out.
+
+    Through our own API, neither can actually happen - every guard (and cursor) we hand out borrows the List, 
+    so the borrow checker won't let you pop or peek_mut while it is alive. 
+    But the list invariants are only as good as the code upholding them, so for callers who'd rather get an error 
+    than a panic, there are `try_` versions of all of them:
+        - try_peek_*: `RefCell::try_borrow(_mut)` instead of `borrow(_mut)`
+        - try_pop_*: check that the node is neither borrowed nor owned by anyone else FIRST, 
+          and only then start unlinking. On error, the list is left exactly as it was.
+    
+    pop_front/pop_back are now just try_pop_front/try_pop_back, with the error marked unreachable.
+    CursorMut::remove_current doesn't get a try_ version: it re-links the neighbours itself, 
+    so by the time it moves the element out, it holds the node's last owner.
+ */
+
+/// Why `try_pop_front`/`try_pop_back` couldn't move an element out of the list.
+///
+/// Only reachable when the list's own invariants are broken: 
+/// the borrow checker won't let a guard into the list live across a pop.
+///
+/// ```compile_fail
+/// use tutorial_too_many_lists::fourth::List;
+///
+/// let mut list = List::new();
+/// list.push_front(1);
+/// let guard = list.peek_front();
+/// let popped = list.try_pop_front();
+/// drop(guard);
+/// ```
+
+#[derive(Debug)]
+pub enum PopError {
+    // the node is currently borrowed through an outstanding guard
+    Borrowed(BorrowMutError),
+    // someone outside of the list still owns the node, so the element can't be moved out
+    Shared,
+}
+
+impl fmt::Display for PopError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PopError::Borrowed(err) => write!(f, "node is still borrowed: {}", err),
+            PopError::Shared => write!(f, "node is still owned outside of the list"),
+        }
+    }
+}
+
+impl Error for PopError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PopError::Borrowed(err) => Some(err),
+            PopError::Shared => None,
+        }
+    }
+}
+
+// checks that `node`, which `owners` pointers of the list own, can be unlinked and unwrapped right now
+fn check_poppable<T>(node: &Rc<RefCell<Node<T>>>, owners: usize) -> Result<(), PopError> {
+    // the guard is dropped right away, we only want to know whether we could get one
+    drop(node.try_borrow_mut().map_err(PopError::Borrowed)?);
+    if Rc::strong_count(node) > owners {
+        return Err(PopError::Shared);
+    }
+    Ok(())
+}
+
+impl<T> List<T> {
+    pub fn try_peek_front(&self) -> Result<Option<Ref<'_, T>>, BorrowError> {
+        self.head.as_ref().map(|node| {
+            node.try_borrow().map(|node| Ref::map(node, |node| &node.elem))
+        }).transpose()
+    }
+
+    pub fn try_peek_front_mut(&mut self) -> Result<Option<RefMut<'_, T>>, BorrowMutError> {
+        self.head.as_ref().map(|node| {
+            node.try_borrow_mut().map(|node| RefMut::map(node, |node| &mut node.elem))
+        }).transpose()
+    }
+
+    pub fn try_peek_back(&self) -> Result<Option<Ref<'_, T>>, BorrowError> {
+        self.tail.as_ref().map(|node| {
+            node.try_borrow().map(|node| Ref::map(node, |node| &node.elem))
+        }).transpose()
+    }
+
+    pub fn try_peek_back_mut(&mut self) -> Result<Option<RefMut<'_, T>>, BorrowMutError> {
+        self.tail.as_ref().map(|node| {
+            node.try_borrow_mut().map(|node| RefMut::map(node, |node| &mut node.elem))
+        }).transpose()
+    }
+}
+
+
 #[cfg(test)]
 mod test {
-    use crate::fourth::List;
+    use crate::fourth::{List, Node, PopError};
+    use std::cell::RefCell;
+    use std::rc::Rc;
 
     #[test]
     fn basics() {
@@ -359,4 +1105,546 @@ mod test {
         assert_eq!(iter.next_back(), None);
         assert_eq!(iter.next(), None);        
     }
+
+    #[test]
+    fn len() {
+        let mut list = List::new();
+        assert_eq!(list.len(), 0);
+        assert!(list.is_empty());
+
+        list.push_front(1); list.push_back(2); list.push_front(3);
+        assert_eq!(list.len(), 3);
+        assert!(!list.is_empty());
+
+        list.pop_back();
+        assert_eq!(list.len(), 2);
+        list.pop_front();
+        assert_eq!(list.len(), 1);
+        list.pop_back();
+        assert_eq!(list.len(), 0);
+        assert!(list.is_empty());
+
+        // popping an empty list must not underflow
+        list.pop_front();
+        list.pop_back();
+        assert_eq!(list.len(), 0);
+    }
+
+    #[test]
+    fn iter() {
+        let mut list = List::new();
+        list.push_front(1); list.push_front(2); list.push_front(3);
+
+        let mut iter = list.iter();
+        assert_eq!(iter.len(), 3);
+        assert_eq!(*iter.next().unwrap(), 3);
+        assert_eq!(*iter.next_back().unwrap(), 1);
+        assert_eq!(*iter.next().unwrap(), 2);
+        assert!(iter.next_back().is_none());
+        assert!(iter.next().is_none());
+
+        // guards from several iterators can be alive at the same time
+        let forward: Vec<_> = list.iter().collect();
+        let backward: Vec<_> = list.iter().rev().collect();
+        assert_eq!(forward.iter().map(|elem| **elem).collect::<Vec<_>>(), vec![3, 2, 1]);
+        assert_eq!(backward.iter().map(|elem| **elem).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(*list.peek_front().unwrap(), 3);
+
+        let empty: List<i32> = List::new();
+        assert!(empty.iter().next().is_none());
+        assert!(empty.iter().next_back().is_none());
+    }
+
+    #[test]
+    fn iter_mut() {
+        let mut list = List::new();
+        list.push_back(1); list.push_back(2); list.push_back(3);
+
+        for mut elem in list.iter_mut() {
+            *elem *= 10;
+        }
+
+        let mut iter = list.iter_mut();
+        let mut first = iter.next().unwrap();
+        let mut last = iter.next_back().unwrap();
+        // both ends at once, without upsetting the RefCells
+        *first += 1;
+        *last += 3;
+        assert_eq!(*iter.next().unwrap(), 20);
+        assert!(iter.next().is_none());
+        assert!(iter.next_back().is_none());
+        drop(first);
+        drop(last);
+
+        assert_eq!(list.pop_front(), Some(11));
+        assert_eq!(list.pop_front(), Some(20));
+        assert_eq!(list.pop_front(), Some(33));
+        assert!(list.iter_mut().next().is_none());
+    }
+
+    // checks all the pointer invariants (Chapter 5.10), and hands out the elements front to back
+    fn check_links<T: Copy>(list: &List<T>) -> Vec<T> {
+        list.check_invariants();
+        list.iter().map(|elem| *elem).collect()
+    }
+
+    // the node at `index`, found by walking the next links
+    fn node_at<T>(list: &List<T>, index: usize) -> Rc<RefCell<Node<T>>> {
+        let mut node = list.head.clone().unwrap();
+        for _ in 0..index {
+            let next = node.borrow().next.clone().unwrap();
+            node = next;
+        }
+        node
+    }
+
+    // where two lists got joined: the nodes on either side point at each other, and have exactly the pointers 
+    // Chapter 5.10 asks for (+1 strong for the Rc we hold here) - nothing leaked, nothing dangling
+    fn assert_joined<T>(list: &List<T>, left: usize) {
+        let (left, right) = (node_at(list, left), node_at(list, left + 1));
+        assert!(Rc::ptr_eq(left.borrow().next.as_ref().unwrap(), &right));
+        assert!(std::ptr::eq(right.borrow().prev.as_ref().unwrap().as_ptr(), Rc::as_ptr(&left)));
+        // owned by its predecessor (or head), seen by right's prev
+        assert_eq!((Rc::strong_count(&left), Rc::weak_count(&left)), (2, 1));
+        // owned by left, plus tail if it's the last node; seen by its successor's prev otherwise
+        let right_is_last = right.borrow().next.is_none();
+        let expected = if right_is_last { (3, 0) } else { (2, 1) };
+        assert_eq!((Rc::strong_count(&right), Rc::weak_count(&right)), expected);
+    }
+
+    // where a list got cut off: its first and last node don't point outside of it anymore,
+    // and are owned by the list's head/tail and their one neighbour (or, for a single node, by head and tail)
+    fn assert_ends<T>(list: &List<T>) {
+        if let (Some(head), Some(tail)) = (&list.head, &list.tail) {
+            assert!(head.borrow().prev.is_none());
+            assert!(tail.borrow().next.is_none());
+            if !Rc::ptr_eq(head, tail) {
+                // owned by head, seen by its successor's prev
+                assert_eq!((Rc::strong_count(head), Rc::weak_count(head)), (1, 1));
+            }
+            // owned by its predecessor (or head) and tail
+            assert_eq!((Rc::strong_count(tail), Rc::weak_count(tail)), (2, 0));
+        }
+    }
+
+    #[test]
+    fn cursor_move() {
+        let mut list = List::new();
+        list.push_back(1); list.push_back(2); list.push_back(3);
+
+        let mut cursor = list.cursor_front_mut();
+        assert_eq!(cursor.index(), Some(0));
+        assert_eq!(*cursor.current().unwrap(), 1);
+        cursor.move_next();
+        assert_eq!(cursor.index(), Some(1));
+        assert_eq!(*cursor.current().unwrap(), 2);
+        cursor.move_next();
+        cursor.move_next();
+        // walked off the back
+        assert_eq!(cursor.index(), None);
+        assert!(cursor.current().is_none());
+        // ... and wrap around
+        cursor.move_next();
+        assert_eq!(*cursor.current().unwrap(), 1);
+        cursor.move_prev();
+        assert_eq!(cursor.index(), None);
+        cursor.move_prev();
+        assert_eq!(cursor.index(), Some(2));
+        *cursor.current().unwrap() = 30;
+
+        drop(cursor);
+        let mut cursor = list.cursor_back_mut();
+        assert_eq!(*cursor.current().unwrap(), 30);
+        cursor.move_prev();
+        assert_eq!(*cursor.current().unwrap(), 2);
+        drop(cursor);
+        assert_eq!(check_links(&list), vec![1, 2, 30]);
+
+        let mut empty: List<i32> = List::new();
+        let mut cursor = empty.cursor_front_mut();
+        assert_eq!(cursor.index(), None);
+        cursor.move_next();
+        assert_eq!(cursor.index(), None);
+        cursor.move_prev();
+        assert!(cursor.current().is_none());
+    }
+
+    #[test]
+    fn cursor_insert() {
+        let mut list = List::new();
+        list.push_back(2);
+
+        let mut cursor = list.cursor_front_mut();
+        cursor.insert_before(1);
+        assert_eq!(cursor.index(), Some(1));
+        cursor.insert_after(4);
+        cursor.insert_after(3);
+        assert_eq!(*cursor.current().unwrap(), 2);
+        cursor.move_next();
+        assert_eq!(*cursor.current().unwrap(), 3);
+        assert_eq!(cursor.index(), Some(2));
+        drop(cursor);
+        assert_eq!(check_links(&list), vec![1, 2, 3, 4]);
+
+        // on the ghost: before == at the back, after == at the front
+        let mut cursor = list.cursor_back_mut();
+        cursor.move_next();
+        cursor.insert_before(5);
+        cursor.insert_after(0);
+        assert_eq!(cursor.index(), None);
+        drop(cursor);
+        assert_eq!(check_links(&list), vec![0, 1, 2, 3, 4, 5]);
+
+        let mut empty = List::new();
+        // first cursor sits on the ghost, the second one on the freshly inserted 1
+        empty.cursor_front_mut().insert_after(1);
+        empty.cursor_front_mut().insert_before(2);
+        assert_eq!(check_links(&empty), vec![2, 1]);
+    }
+
+    #[test]
+    fn cursor_remove() {
+        let mut list = List::new();
+        list.push_back(1); list.push_back(2); list.push_back(3); list.push_back(4);
+
+        let mut cursor = list.cursor_front_mut();
+        cursor.move_next();
+        assert_eq!(cursor.remove_current(), Some(2));
+        // the next element slides in
+        assert_eq!(cursor.index(), Some(1));
+        assert_eq!(*cursor.current().unwrap(), 3);
+        drop(cursor);
+        assert_eq!(check_links(&list), vec![1, 3, 4]);
+
+        let mut cursor = list.cursor_back_mut();
+        assert_eq!(cursor.remove_current(), Some(4));
+        assert_eq!(cursor.index(), None);
+        assert_eq!(cursor.remove_current(), None);
+        drop(cursor);
+        assert_eq!(check_links(&list), vec![1, 3]);
+
+        let mut cursor = list.cursor_front_mut();
+        assert_eq!(cursor.remove_current(), Some(1));
+        assert_eq!(cursor.remove_current(), Some(3));
+        assert_eq!(cursor.remove_current(), None);
+        drop(cursor);
+        assert_eq!(check_links(&list), vec![]);
+        assert_eq!(list.pop_front(), None);
+        assert_eq!(list.pop_back(), None);
+    }
+
+    #[test]
+    fn cursor_split() {
+        let mut list = List::new();
+        for elem in 1..=6 { list.push_back(elem); }
+
+        let mut cursor = list.cursor_front_mut();
+        cursor.move_next(); cursor.move_next();
+        let back = cursor.split_after();
+        assert_eq!(cursor.index(), Some(2));
+        let front = cursor.split_before();
+        assert_eq!(cursor.index(), Some(0));
+        assert_eq!(*cursor.current().unwrap(), 3);
+
+        drop(cursor);
+        assert_eq!(check_links(&front), vec![1, 2]);
+        assert_eq!(check_links(&list), vec![3]);
+        assert_eq!(check_links(&back), vec![4, 5, 6]);
+
+        // nothing to split off at the ends
+        let mut cursor = list.cursor_front_mut();
+        assert!(cursor.split_before().is_empty());
+        assert!(cursor.split_after().is_empty());
+        drop(cursor);
+        assert_eq!(check_links(&list), vec![3]);
+
+        // the ghost splits off everything
+        let mut cursor = list.cursor_front_mut();
+        cursor.move_prev();
+        let all = cursor.split_after();
+        drop(cursor);
+        assert_eq!(check_links(&all), vec![3]);
+        assert_eq!(check_links(&list), vec![]);
+    }
+
+    #[test]
+    fn cursor_splice() {
+        let mut list = List::new();
+        list.push_back(1); list.push_back(5);
+
+        let mut middle = List::new();
+        middle.push_back(3);
+
+        let mut before = List::new();
+        before.push_back(2);
+
+        let mut after = List::new();
+        after.push_back(4);
+
+        let mut cursor = list.cursor_front_mut();
+        cursor.splice_after(middle);
+        cursor.move_next();
+        assert_eq!(*cursor.current().unwrap(), 3);
+        cursor.splice_before(before);
+        assert_eq!(cursor.index(), Some(2));
+        cursor.splice_after(after);
+        cursor.splice_after(List::new());
+        cursor.splice_before(List::new());
+        drop(cursor);
+        assert_eq!(check_links(&list), vec![1, 2, 3, 4, 5]);
+
+        // on the ghost: before == at the back, after == at the front
+        let mut front = List::new();
+        front.push_back(-1); front.push_back(0);
+        let mut back = List::new();
+        back.push_back(6); back.push_back(7);
+
+        let mut cursor = list.cursor_back_mut();
+        cursor.move_next();
+        cursor.splice_before(back);
+        cursor.splice_after(front);
+        drop(cursor);
+        assert_eq!(check_links(&list), vec![-1, 0, 1, 2, 3, 4, 5, 6, 7]);
+
+        let mut empty = List::new();
+        empty.cursor_front_mut().splice_after(list);
+        assert_eq!(check_links(&empty), vec![-1, 0, 1, 2, 3, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn append() {
+        let mut list = List::new();
+        list.push_back(1); list.push_back(2);
+        let mut other = List::new();
+        other.push_back(3); other.push_back(4);
+
+        list.append(&mut other);
+        assert_eq!(check_links(&list), vec![1, 2, 3, 4]);
+        assert_eq!(check_links(&other), vec![]);
+        assert_joined(&list, 1);
+        assert_ends(&list);
+
+        let mut other = List::new();
+        other.push_back(-1); other.push_back(0);
+        list.prepend_list(&mut other);
+        assert_eq!(check_links(&list), vec![-1, 0, 1, 2, 3, 4]);
+        assert_eq!(check_links(&other), vec![]);
+        assert_joined(&list, 1);
+        assert_ends(&list);
+
+        // empty on either side
+        list.append(&mut other);
+        list.prepend_list(&mut other);
+        assert_eq!(check_links(&list), vec![-1, 0, 1, 2, 3, 4]);
+
+        other.append(&mut list);
+        assert_eq!(check_links(&other), vec![-1, 0, 1, 2, 3, 4]);
+        assert_eq!(check_links(&list), vec![]);
+        list.prepend_list(&mut other);
+        assert_eq!(check_links(&list), vec![-1, 0, 1, 2, 3, 4]);
+
+        // both ends still work after all the re-wiring
+        list.push_back(5);
+        list.push_front(-2);
+        assert_eq!(list.pop_back(), Some(5));
+        assert_eq!(list.pop_front(), Some(-2));
+        assert_eq!(check_links(&list), vec![-1, 0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn split_off() {
+        for at in 0..=6 {
+            let mut list = List::new();
+            for elem in 0..6 { list.push_back(elem); }
+
+            let back = list.split_off(at);
+            assert_eq!(check_links(&list), (0..at).collect::<Vec<_>>());
+            assert_eq!(check_links(&back), (at..6).collect::<Vec<_>>());
+            assert_ends(&list);
+            assert_ends(&back);
+        }
+
+        let mut empty: List<i32> = List::new();
+        assert!(empty.split_off(0).is_empty());
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn split_off_out_of_bounds() {
+        let mut list = List::new();
+        list.push_back(1);
+        list.split_off(2);
+    }
+
+    #[test]
+    fn pop_error_auto_traits() {
+        // List, Iter, IterMut, IntoIter and CursorMut are all invariant and neither Send nor Sync,
+        // which a test can't check - that's the compile_fail doctests on each of them. Their errors do cross threads:
+        crate::test_util::assert_send_sync::<PopError>();
+    }
+
+    #[test]
+    fn long_drop() {
+        crate::test_util::drop_on_small_stack(|| {
+            let mut list = List::new();
+            for elem in 0..crate::test_util::LONG {
+                if elem % 2 == 0 { list.push_back(elem) } else { list.push_front(elem) }
+            }
+            list
+        });
+    }
+
+    #[test]
+    fn no_leaks() {
+        use crate::test_util::{assert_no_leaks, DropTracker};
+
+        let tracker = DropTracker::new(12);
+        assert_no_leaks(|| {
+            let mut list = List::new();
+            for id in 0..4 {
+                list.push_back(tracker.counter(id));
+            }
+            assert_eq!(list.pop_front().map(|elem| elem.id()), Some(0));
+            assert_eq!(list.pop_back().map(|elem| elem.id()), Some(3));
+            assert_eq!(list.iter().count(), 2);
+            assert_eq!(list.iter_mut().rev().count(), 2);
+            assert_eq!(list.peek_front().map(|elem| elem.id()), Some(1));
+
+            // cursor surgery, appending and splitting: plenty of chances to forget a link
+            let mut cursor = list.cursor_front_mut();
+            cursor.insert_after(tracker.counter(4));
+            cursor.move_next();
+            assert_eq!(cursor.remove_current().map(|elem| elem.id()), Some(4));
+            cursor.insert_before(tracker.counter(5));
+            let mut split = cursor.split_after();
+            drop(cursor);
+
+            let mut other = List::new();
+            other.push_front(tracker.counter(6));
+            other.push_front(tracker.counter(7));
+            list.append(&mut other);
+            split.prepend_list(&mut list);
+            let tail = split.split_off(2);
+            split.push_back(tracker.counter(8));
+
+            let mut iter = tail.into_iter();
+            iter.next_back();
+            drop(iter);
+
+            let mut list = List::new();
+            for id in 9..12 {
+                list.push_front(tracker.counter(id));
+            }
+        });
+        tracker.assert_all_dropped_once();
+    }
+
+    #[test]
+    fn invariants() {
+        let mut list = List::new();
+        list.check_invariants();
+        list.push_back(1);
+        list.check_invariants();
+        list.push_front(0);
+        list.push_back(2);
+        list.check_invariants();
+        // peeking hands out guards, but never touches the pointer counts
+        {
+            let _front = list.peek_front();
+            let _back = list.peek_back();
+            list.check_invariants();
+        }
+        assert_eq!(list.pop_back(), Some(2));
+        list.check_invariants();
+        assert_eq!(list.pop_front(), Some(0));
+        list.check_invariants();
+        assert_eq!(list.pop_back(), Some(1));
+        list.check_invariants();
+    }
+
+    #[test]
+    #[should_panic(expected = "strong pointers")]
+    fn invariants_catch_extra_pointers() {
+        let mut list = List::new();
+        list.push_back(1); list.push_back(2);
+        // an extra owner that the list doesn't know about
+        let _extra = list.head.clone();
+        list.check_invariants();
+    }
+
+    #[test]
+    fn try_peek() {
+        let mut list = List::new();
+        assert!(list.try_peek_front().unwrap().is_none());
+        assert!(list.try_peek_back_mut().unwrap().is_none());
+
+        list.push_back(1); list.push_back(2);
+        assert_eq!(*list.try_peek_front().unwrap().unwrap(), 1);
+        assert_eq!(*list.try_peek_back().unwrap().unwrap(), 2);
+        *list.try_peek_front_mut().unwrap().unwrap() = 10;
+        *list.try_peek_back_mut().unwrap().unwrap() = 20;
+
+        // sneak a guard past the borrow checker, by going through a second owner of the nodes
+        let head = list.head.clone().unwrap();
+        let tail = list.tail.clone().unwrap();
+        {
+            let _guard = head.borrow_mut();
+            let _shared = tail.borrow();
+            assert!(list.try_peek_front().is_err());
+            assert!(list.try_peek_front_mut().is_err());
+            assert_eq!(*list.try_peek_back().unwrap().unwrap(), 20);
+            assert!(list.try_peek_back_mut().is_err());
+        }
+        assert_eq!(*list.try_peek_front().unwrap().unwrap(), 10);
+    }
+
+    // Neither error can be caused through the public API: every guard and cursor borrows the list, 
+    // so pop can't even be called while one is alive (see the compile_fail doctest on PopError).
+    // The only way to get a guard (or an owner) past the borrow checker is through the private fields.
+    #[test]
+    fn try_pop() {
+        let mut list = List::new();
+        assert_eq!(list.try_pop_front().unwrap(), None);
+        assert_eq!(list.try_pop_back().unwrap(), None);
+        list.push_back(1); list.push_back(2); list.push_back(3);
+
+        let head = list.head.clone().unwrap();
+        {
+            let _guard = head.borrow();
+            assert!(matches!(list.try_pop_front(), Err(PopError::Borrowed(_))));
+        }
+        assert!(matches!(list.try_pop_front(), Err(PopError::Shared)));
+        // nothing happened to the list
+        assert_eq!(list.iter().map(|elem| *elem).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(list.len(), 3);
+        drop(head);
+        list.check_invariants();
+        assert_eq!(list.try_pop_front().unwrap(), Some(1));
+
+        let tail = list.tail.clone().unwrap();
+        assert!(matches!(list.try_pop_back(), Err(PopError::Shared)));
+        assert_eq!(list.try_peek_back().unwrap().map(|elem| *elem), Some(3));
+        drop(tail);
+        assert_eq!(list.try_pop_back().unwrap(), Some(3));
+        assert_eq!(list.try_pop_back().unwrap(), Some(2));
+        assert_eq!(list.try_pop_front().unwrap(), None);
+        list.check_invariants();
+    }
+
+    #[test]
+    fn drop_with_shared_node() {
+        use crate::test_util::DropTracker;
+
+        // a node owned outside of the list doesn't stop Drop: it just outlives the list, the rest goes
+        let tracker = DropTracker::new(3);
+        let mut list = List::new();
+        (0..3).for_each(|id| list.push_back(tracker.counter(id)));
+        let middle = list.head.as_ref().unwrap().borrow().next.clone().unwrap();
+        drop(list);
+        assert_eq!((tracker.drops(0), tracker.drops(1), tracker.drops(2)), (1, 0, 1));
+        assert!(middle.borrow().next.is_none());
+        drop(middle);
+        tracker.assert_all_dropped_once();
+    }
 }
\ No newline at end of file
@@ -3,3 +3,14 @@ pub mod second;
 pub mod third;
 pub mod fourth;
 pub mod fifth;
+pub mod sixth;
+
+pub mod concurrent;
+
+pub mod traits;
+
+#[cfg(test)]
+mod test_util;
+
+#[cfg(test)]
+mod model;
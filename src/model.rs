@@ -0,0 +1,405 @@
+// Model checking: random operation sequences, compared against VecDeque
+
+/* 
+    The hand-written tests only ever try the sequences somebody thought of. 
+    This throws thousands of random ones at a list instead, and replays each of them on a `VecDeque` - 
+    the "model", which we trust to be right. After every single operation, both have to agree on the result.
+
+    When they don't (or the list panics), the failing sequence is usually long and full of noise.
+    So we shrink it, like proptest/QuickCheck do: keep throwing out chunks of operations, and simplifying the values,
+    as long as the shorter sequence still fails. What's left is (close to) the minimal reproduction, 
+    which is what ends up in the panic message.
+
+    Every list maps its own operations onto the model's:
+        a stack (second) works on the front of the VecDeque,
+        a queue (fifth) pushes at the back and pops at the front,
+        a deque (fourth) gets to use everything.
+
+    No external crates here, so the randomness comes from the little xorshift generator in test_util.
+    The seed is fixed, so failures are reproducible; set MODEL_SEED to try different sequences.
+ */
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::test_util::Rng;
+use crate::{fifth, fourth, second};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Op {
+    PushFront(i32),
+    PushBack(i32),
+    PopFront,
+    PopBack,
+    PeekFront,
+    PeekBack,
+    // overwrite the element with the value, through a mutable peek
+    SetFront(i32),
+    SetBack(i32),
+    // add the value to every element, through iter_mut
+    AddAll(i32),
+    Iter,
+    IterRev,
+    Len,
+}
+
+// what an operation hands back, in a form that can be compared with the model's
+#[derive(Debug, PartialEq)]
+pub enum Observed {
+    Nothing,
+    Elem(Option<i32>),
+    Elems(Vec<i32>),
+    Len(usize),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Kind {
+    PushFront, PushBack, PopFront, PopBack, PeekFront, PeekBack, SetFront, SetBack, AddAll, Iter, IterRev, Len,
+}
+
+pub trait Subject: Default {
+    // the operations this list supports
+    const KINDS: &'static [Kind];
+
+    fn apply(&mut self, op: Op) -> Observed;
+
+    // a chance to check internal invariants after every operation
+    fn check(&self) {}
+}
+
+impl Op {
+    fn generate(kind: Kind, rng: &mut Rng) -> Op {
+        let value = (rng.next() % 100) as i32;
+        match kind {
+            Kind::PushFront => Op::PushFront(value),
+            Kind::PushBack => Op::PushBack(value),
+            Kind::PopFront => Op::PopFront,
+            Kind::PopBack => Op::PopBack,
+            Kind::PeekFront => Op::PeekFront,
+            Kind::PeekBack => Op::PeekBack,
+            Kind::SetFront => Op::SetFront(value),
+            Kind::SetBack => Op::SetBack(value),
+            Kind::AddAll => Op::AddAll(value),
+            Kind::Iter => Op::Iter,
+            Kind::IterRev => Op::IterRev,
+            Kind::Len => Op::Len,
+        }
+    }
+
+    // the same operation with a "simpler" value, if there is one
+    fn simplified(self) -> Option<Op> {
+        match self {
+            Op::PushFront(v) if v != 0 => Some(Op::PushFront(0)),
+            Op::PushBack(v) if v != 0 => Some(Op::PushBack(0)),
+            Op::SetFront(v) if v != 0 => Some(Op::SetFront(0)),
+            Op::SetBack(v) if v != 0 => Some(Op::SetBack(0)),
+            Op::AddAll(v) if v != 0 => Some(Op::AddAll(0)),
+            _ => None,
+        }
+    }
+}
+
+fn model_apply(model: &mut VecDeque<i32>, op: Op) -> Observed {
+    match op {
+        Op::PushFront(v) => { model.push_front(v); Observed::Nothing }
+        Op::PushBack(v) => { model.push_back(v); Observed::Nothing }
+        Op::PopFront => Observed::Elem(model.pop_front()),
+        Op::PopBack => Observed::Elem(model.pop_back()),
+        Op::PeekFront => Observed::Elem(model.front().copied()),
+        Op::PeekBack => Observed::Elem(model.back().copied()),
+        Op::SetFront(v) => Observed::Elem(model.front_mut().map(|elem| std::mem::replace(elem, v))),
+        Op::SetBack(v) => Observed::Elem(model.back_mut().map(|elem| std::mem::replace(elem, v))),
+        Op::AddAll(v) => {
+            model.iter_mut().for_each(|elem| *elem = elem.wrapping_add(v));
+            Observed::Nothing
+        }
+        Op::Iter => Observed::Elems(model.iter().copied().collect()),
+        Op::IterRev => Observed::Elems(model.iter().rev().copied().collect()),
+        Op::Len => Observed::Len(model.len()),
+    }
+}
+
+
+// The lists under test
+
+impl Subject for second::List<i32> {
+    const KINDS: &'static [Kind] = &[
+        Kind::PushFront, Kind::PushFront, Kind::PopFront, Kind::PeekFront, Kind::SetFront, Kind::AddAll, Kind::Iter, Kind::Len,
+    ];
+
+    fn apply(&mut self, op: Op) -> Observed {
+        match op {
+            Op::PushFront(v) => { self.push(v); Observed::Nothing }
+            Op::PopFront => Observed::Elem(self.pop()),
+            Op::PeekFront => Observed::Elem(self.peek().copied()),
+            Op::SetFront(v) => Observed::Elem(self.peek_mut().map(|elem| std::mem::replace(elem, v))),
+            Op::AddAll(v) => {
+                self.iter_mut().for_each(|elem| *elem = elem.wrapping_add(v));
+                Observed::Nothing
+            }
+            Op::Iter => Observed::Elems(self.iter().copied().collect()),
+            Op::Len => {
+                // len() and the iterators' exact sizes have to agree with each other as well
+                assert_eq!(self.iter().len(), self.len(), "iter().len() disagrees with len()");
+                Observed::Len(self.len())
+            }
+            _ => unreachable!("{:?} isn't a stack operation", op),
+        }
+    }
+}
+
+impl Subject for fourth::List<i32> {
+    const KINDS: &'static [Kind] = &[
+        Kind::PushFront, Kind::PushBack, Kind::PopFront, Kind::PopBack, Kind::PeekFront, Kind::PeekBack,
+        Kind::SetFront, Kind::SetBack, Kind::AddAll, Kind::Iter, Kind::IterRev, Kind::Len,
+    ];
+
+    fn apply(&mut self, op: Op) -> Observed {
+        match op {
+            Op::PushFront(v) => { self.push_front(v); Observed::Nothing }
+            Op::PushBack(v) => { self.push_back(v); Observed::Nothing }
+            Op::PopFront => Observed::Elem(self.pop_front()),
+            Op::PopBack => Observed::Elem(self.pop_back()),
+            Op::PeekFront => Observed::Elem(self.peek_front().map(|elem| *elem)),
+            Op::PeekBack => Observed::Elem(self.peek_back().map(|elem| *elem)),
+            Op::SetFront(v) => Observed::Elem(self.peek_front_mut().map(|mut elem| std::mem::replace(&mut *elem, v))),
+            Op::SetBack(v) => Observed::Elem(self.peek_back_mut().map(|mut elem| std::mem::replace(&mut *elem, v))),
+            Op::AddAll(v) => {
+                self.iter_mut().for_each(|mut elem| *elem = elem.wrapping_add(v));
+                Observed::Nothing
+            }
+            Op::Iter => Observed::Elems(self.iter().map(|elem| *elem).collect()),
+            Op::IterRev => Observed::Elems(self.iter().rev().map(|elem| *elem).collect()),
+            Op::Len => {
+                assert_eq!(self.iter().len(), self.len(), "iter().len() disagrees with len()");
+                Observed::Len(self.len())
+            }
+        }
+    }
+
+    fn check(&self) {
+        self.check_invariants();
+    }
+}
+
+impl Subject for fifth::List<i32> {
+    const KINDS: &'static [Kind] = &[
+        Kind::PushBack, Kind::PushBack, Kind::PopFront, Kind::PeekFront, Kind::SetFront, Kind::AddAll, Kind::Iter,
+    ];
+
+    fn apply(&mut self, op: Op) -> Observed {
+        match op {
+            Op::PushBack(v) => { self.push(v); Observed::Nothing }
+            Op::PopFront => Observed::Elem(self.pop()),
+            Op::PeekFront => Observed::Elem(self.peek().copied()),
+            Op::SetFront(v) => Observed::Elem(self.peek_mut().map(|elem| std::mem::replace(elem, v))),
+            Op::AddAll(v) => {
+                self.iter_mut().for_each(|elem| *elem = elem.wrapping_add(v));
+                Observed::Nothing
+            }
+            Op::Iter => Observed::Elems(self.iter().copied().collect()),
+            _ => unreachable!("{:?} isn't a queue operation", op),
+        }
+    }
+}
+
+
+// The engine
+
+pub struct Failure {
+    // the (shrunk) sequence, up to and including the operation that failed
+    pub ops: Vec<Op>,
+    pub reason: String,
+}
+
+impl fmt::Debug for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.reason)?;
+        writeln!(f, "minimal failing sequence ({} operations):", self.ops.len())?;
+        for op in &self.ops {
+            writeln!(f, "    {:?}", op)?;
+        }
+        Ok(())
+    }
+}
+
+// replays `ops` on a fresh list and a fresh model; on failure, returns the failing prefix and what went wrong
+fn run<S: Subject>(ops: &[Op]) -> Result<(), Failure> {
+    let mut model = VecDeque::new();
+    let mut list = S::default();
+    for (step, &op) in ops.iter().enumerate() {
+        let expected = model_apply(&mut model, op);
+        let actual = panic::catch_unwind(AssertUnwindSafe(|| {
+            let actual = list.apply(op);
+            list.check();
+            actual
+        }));
+        let reason = match actual {
+            Ok(actual) if actual == expected => continue,
+            Ok(actual) => format!("step {}: {:?} returned {:?}, the model says {:?}", step, op, actual, expected),
+            Err(panic) => {
+                let message = panic.downcast_ref::<String>().cloned()
+                    .or_else(|| panic.downcast_ref::<&str>().map(|msg| msg.to_string()))
+                    .unwrap_or_default();
+                // whatever state the list is in now, it's not worth dropping
+                std::mem::forget(list);
+                return Err(Failure { ops: ops[..=step].to_vec(), reason: format!("step {}: {:?} panicked: {}", step, op, message) });
+            }
+        };
+        return Err(Failure { ops: ops[..=step].to_vec(), reason });
+    }
+    Ok(())
+}
+
+// throws out as much of a failing sequence as possible, while keeping it failing
+fn shrink<S: Subject>(mut failure: Failure) -> Failure {
+    loop {
+        let mut progress = false;
+
+        // remove chunks, from half the sequence down to single operations
+        let mut chunk = failure.ops.len() / 2;
+        while chunk > 0 {
+            let mut start = 0;
+            while start + chunk <= failure.ops.len() {
+                let mut candidate = failure.ops.clone();
+                candidate.drain(start..start + chunk);
+                match run::<S>(&candidate) {
+                    Err(smaller) => { failure = smaller; progress = true; }
+                    Ok(()) => start += chunk,
+                }
+            }
+            chunk /= 2;
+        }
+
+        // simplify the values that are left
+        for i in 0..failure.ops.len() {
+            if let Some(simpler) = failure.ops.get(i).and_then(|op| op.simplified()) {
+                let mut candidate = failure.ops.clone();
+                candidate[i] = simpler;
+                if let Err(simpler) = run::<S>(&candidate) {
+                    failure = simpler;
+                    progress = true;
+                }
+            }
+        }
+
+        if !progress {
+            return failure;
+        }
+    }
+}
+
+// runs `cases` random sequences of up to `max_len` operations; returns the first failure, shrunk
+pub fn find_failure<S: Subject>(seed: u64, cases: usize, max_len: usize) -> Option<Failure> {
+    let mut rng = Rng::new(seed);
+    for _ in 0..cases {
+        let len = (rng.next() % max_len as u64) as usize + 1;
+        let ops: Vec<Op> = (0..len)
+            .map(|_| {
+                let kind = S::KINDS[(rng.next() % S::KINDS.len() as u64) as usize];
+                Op::generate(kind, &mut rng)
+            })
+            .collect();
+        if let Err(failure) = run::<S>(&ops) {
+            return Some(shrink::<S>(failure));
+        }
+    }
+    None
+}
+
+pub fn check<S: Subject>(cases: usize, max_len: usize) {
+    let seed = std::env::var("MODEL_SEED").ok()
+        .and_then(|seed| seed.parse().ok())
+        .unwrap_or(0x5eed_1157);
+    if let Some(failure) = find_failure::<S>(seed, cases, max_len) {
+        panic!("model check failed (MODEL_SEED={})\n{:?}", seed, failure);
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::model::{check, find_failure, Kind, Observed, Op, Subject};
+    use crate::{fifth, fourth, second};
+    use std::collections::VecDeque;
+
+    // 300 sequences of up to 200 operations each: tens of thousands of operations per list
+    const CASES: usize = 300;
+    const MAX_LEN: usize = 200;
+
+    #[test]
+    fn second_matches_model() {
+        check::<second::List<i32>>(CASES, MAX_LEN);
+    }
+
+    #[test]
+    fn fourth_matches_model() {
+        check::<fourth::List<i32>>(CASES, MAX_LEN);
+    }
+
+    #[test]
+    fn fifth_matches_model() {
+        check::<fifth::List<i32>>(CASES, MAX_LEN);
+    }
+
+    // a deliberately broken queue: it loses every push after the third element
+    #[derive(Default)]
+    struct Forgetful(VecDeque<i32>);
+
+    impl Subject for Forgetful {
+        const KINDS: &'static [Kind] = &[Kind::PushBack, Kind::PushBack, Kind::PopFront, Kind::PeekFront, Kind::Iter];
+
+        fn apply(&mut self, op: Op) -> Observed {
+            match op {
+                Op::PushBack(v) => {
+                    if self.0.len() < 3 {
+                        self.0.push_back(v);
+                    }
+                    Observed::Nothing
+                }
+                Op::PopFront => Observed::Elem(self.0.pop_front()),
+                Op::PeekFront => Observed::Elem(self.0.front().copied()),
+                Op::Iter => Observed::Elems(self.0.iter().copied().collect()),
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    // and one that panics instead of giving a wrong answer
+    #[derive(Default)]
+    struct Grumpy(VecDeque<i32>);
+
+    impl Subject for Grumpy {
+        const KINDS: &'static [Kind] = &[Kind::PushBack, Kind::PopFront];
+
+        fn apply(&mut self, op: Op) -> Observed {
+            match op {
+                Op::PushBack(v) => { self.0.push_back(v); Observed::Nothing }
+                Op::PopFront => Observed::Elem(self.0.pop_front()),
+                _ => unreachable!(),
+            }
+        }
+
+        fn check(&self) {
+            assert!(!self.0.contains(&42), "no 42s allowed");
+        }
+    }
+
+    #[test]
+    fn shrinks_to_minimal_sequence() {
+        let failure = find_failure::<Forgetful>(1, 100, 100).expect("the bug should be found");
+        // three pushes to fill it up, one that gets lost, and one look at the result
+        assert_eq!(failure.ops.len(), 5, "{:?}", failure);
+        assert_eq!(&failure.ops[..4], &[Op::PushBack(0); 4], "{:?}", failure);
+        assert!(matches!(failure.ops[4], Op::PopFront | Op::PeekFront | Op::Iter), "{:?}", failure);
+    }
+
+    #[test]
+    fn shrinks_panics() {
+        let failure = find_failure::<Grumpy>(1, 1000, 100).expect("the bug should be found");
+        // only the one value that matters survives, so it doesn't get simplified to 0
+        assert_eq!(failure.ops, vec![Op::PushBack(42)], "{:?}", failure);
+        assert!(failure.reason.contains("no 42s allowed"), "{:?}", failure);
+    }
+}
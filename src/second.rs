@@ -18,8 +18,22 @@
 
 // 3.2 
 // making it generic, using T type substitute
+
+/// Covariant over `T`, and `Send`/`Sync` exactly when `T` is - just like the `Box`es it's made of.
+///
+/// ```compile_fail
+/// // Rc isn't Send, so a stack of Rc's can't be sent to another thread either
+/// use std::rc::Rc;
+/// use tutorial_too_many_lists::second::List;
+///
+/// let mut list = List::new();
+/// list.push(Rc::new(1));
+/// std::thread::spawn(move || drop(list));
+/// ```
 pub struct List<T> {
     head: Link<T>,
+    // kept up to date by push and pop, so len() is O(1) instead of a full walk
+    len: usize,
 }
 
 // 3.1 + 3.2
@@ -34,17 +48,18 @@ struct Node<T> {
 
 impl<T> List<T> {
     pub fn new() -> Self {
-        List { head: None }
+        List { head: None, len: 0 }
     }
 
     pub fn push(&mut self, elem: T) {
         let new_node = Box::new(Node {
-            elem: elem,
+            elem,
             // mem::replace(&mut TARGET_VALUE, None) is SO incredibly common, that Option comes with a dedicated method for it
             next: self.head.take(),
         });
 
         self.head = Some(new_node);
+        self.len += 1;
     }
 
     pub fn pop(&mut self) -> Option<T> {
@@ -52,10 +67,19 @@ impl<T> List<T> {
         // match option { None => None, Some(x) => Some(y) } is equally common, so there is `map` for that
         self.head.take().map( |node| {
             self.head = node.next;
+            self.len -= 1;
             node.elem
         })
     }
 
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
     // 3.3
     pub fn peek(&self) -> Option<&T> {
         // map takes the `self` by value, which would move the Option val out -> we need to use the `as_ref` method for Option<T>
@@ -74,6 +98,12 @@ impl<T> List<T> {
 }
 
 
+impl<T> Default for List<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T> Drop for List<T> {
     fn drop(&mut self) {
         // mem::replace(&mut TARGET_VALUE, None) is SO incredibly common, that Option comes with a dedicated method for it
@@ -114,11 +144,7 @@ impl<T> Drop for List<T> {
 // Tuple structs are an alternative form of struct, useful for trivial wrappers around other types
 pub struct IntoIter<T>(List<T>);
 
-impl<T> List<T> {
-    pub fn into_iter(self) -> IntoIter<T> {
-        IntoIter(self)
-    }
-}
+// `list.into_iter()` comes from the IntoIterator impl further down (3.7), so `for x in list` works too
 
 impl<T> Iterator for IntoIter<T> {
     type Item = T;
@@ -126,8 +152,15 @@ impl<T> Iterator for IntoIter<T> {
         // access fields of a tuple struct numerically
         self.0.pop()    // tuple index can be queried with dot notation
     }
+
+    // the list knows its length, so the iterator knows exactly how much is left
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.0.len, Some(self.0.len))
+    }
 }
 
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
 // 3.5
 
 // Iter implementation cannot rely on pre-existing List features
@@ -139,6 +172,8 @@ impl<T> Iterator for IntoIter<T> {
 // Iter is generic over *some* lifetime, it does not care
 pub struct Iter<'a, T> {
     next: Option<&'a Node<T>>,
+    // elements left to yield -> needed for an exact size_hint
+    len: usize,
 }
 
 // no lifetimes here - List does not have any associated lifetimes
@@ -149,7 +184,8 @@ impl<T> List<T> {
         // note: lifetime elision COULD be applied here; `pub fn iter(&self) -> Iter<'T> {…}` is equivalent to our signature
         Iter {
             // Option<T>.as_deref() does just that, while considering the possibility of a None
-            next: self.head.as_deref()
+            next: self.head.as_deref(),
+            len: self.len,
         }
     }
     // also: while using elision, one can hint at the hidden presence of a lifetime by using
@@ -167,11 +203,18 @@ impl<'a, T> Iterator for Iter<'a, T> {
             // next is a Box inside the Option, which we need to unpack
             // Option<T>.as_deref() does just that, while considering the possibility of a None
             self.next = node.next.as_deref();
+            self.len -= 1;
             &node.elem
         })
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
 }
 
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
 /* 
     Regarding `.as_deref()`:
     The as_deref and as_deref_mut functions are stable as of Rust 1.40. 
@@ -212,13 +255,24 @@ impl<'a, T> Iterator for Iter<'a, T> {
 
 // Start by taking the Iter code and making EVERYTHING mutable!
 
+/// Like `&'a mut T`, `IterMut` is invariant over `T`:
+/// otherwise it could be used to write short-lived values into a list of long-lived ones.
+///
+/// ```compile_fail
+/// use tutorial_too_many_lists::second::IterMut;
+///
+/// fn shorten<'i, 'a>(iter: IterMut<'i, &'static str>) -> IterMut<'i, &'a str> {
+///     iter
+/// }
+/// ```
 pub struct IterMut<'a, T> {
     next: Option<&'a mut Node<T>>,
+    len: usize,
 }
 
 impl<T> List<T> {
     pub fn iter_mut(&mut self) -> IterMut<'_, T> {
-        IterMut { next: self.head.as_deref_mut() }  // deref must be mut now, and so must the ref to self
+        IterMut { next: self.head.as_deref_mut(), len: self.len }  // deref must be mut now, and so must the ref to self
     }
 }
 
@@ -229,9 +283,14 @@ impl<'a, T> Iterator for IterMut<'a, T> {
         self.next.take().map( |node| {      // to avoid the tedium of sharing mut references-- just TAKE the value, i.e. the mut ref to the Node
             // now we have exclusive ownership over the mut ref, which has been removed from its original location - while its value stays in the List
             self.next = node.next.as_deref_mut();
+            self.len -= 1;
             &mut node.elem
         })
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
     /* 
         &mut isn't Copy (if you copied an &mut, you'd have two &mut's to the same location in memory, which is forbidden). 
         Instead, we take the Option to get it. 
@@ -245,6 +304,117 @@ impl<'a, T> Iterator for IterMut<'a, T> {
      */
 }
 
+impl<T> ExactSizeIterator for IterMut<'_, T> {}
+
+// 3.7
+// Standard traits - so the stack drops into ordinary Rust code
+/* 
+    Everything below is built on top of push and the three iterators; no new pointer juggling needed.
+    
+    Keep in mind that this is a STACK: 
+    - `collect()` and `extend()` push elements one by one, so the LAST element yielded ends up on top
+    - iteration (and therefore Debug, comparisons and Hash) goes from the top of the stack downwards
+    - Clone is the exception: it must give back an equal list, so it rebuilds the nodes in iteration order
+ */
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+impl<T> FromIterator<T> for List<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = List::new();
+        list.extend(iter);
+        list
+    }
+}
+
+impl<T> Extend<T> for List<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for elem in iter {
+            self.push(elem);
+        }
+    }
+}
+
+impl<T> IntoIterator for List<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter(self)
+    }
+}
+
+impl<'a, T> IntoIterator for &'a List<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut List<T> {
+    type Item = &'a mut T;
+    type IntoIter = IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for List<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self).finish()
+    }
+}
+
+impl<T: Clone> Clone for List<T> {
+    fn clone(&self) -> Self {
+        // pushing would reverse the order, so we append at the end of the new list instead,
+        // keeping a mutable reference to the last (empty) link around
+        let mut new_list = List::new();
+        let mut cur_link = &mut new_list.head;
+        for elem in self {
+            let new_node = cur_link.insert(Box::new(Node { elem: elem.clone(), next: None }));
+            cur_link = &mut new_node.next;
+        }
+        new_list.len = self.len;
+        new_list
+    }
+}
+
+impl<T: PartialEq> PartialEq for List<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other)
+    }
+}
+
+impl<T: Eq> Eq for List<T> {}
+
+impl<T: PartialOrd> PartialOrd for List<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other)
+    }
+}
+
+impl<T: Ord> Ord for List<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other)
+    }
+}
+
+impl<T: Hash> Hash for List<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // hash the length first, just like Vec does, so nested lists cannot collide by shifting elements around
+        state.write_usize(self.len);
+        for elem in self {
+            elem.hash(state);
+        }
+    }
+}
+
 
 #[cfg(test)]
 mod test {
@@ -276,6 +446,7 @@ mod test {
     }
 
     #[test]
+    #[allow(clippy::option_map_unit_fn)]
     fn peek() {
         // test peeking empty list
         let mut list = List::new();
@@ -320,6 +491,7 @@ mod test {
     }
 
     #[test]
+    #[allow(clippy::option_map_unit_fn)]
     fn iter_mut() {
         let mut list = List::new();
         list.push(1); list.push(2); list.push(3);
@@ -339,4 +511,186 @@ mod test {
         assert_eq!(iter.next(), None);
 
     }
-}
+
+    #[test]
+    fn from_iter_and_extend() {
+        // stack semantics: the last element collected is on top
+        let mut list: List<i32> = (1..=3).collect();
+        assert_eq!(list.peek(), Some(&3));
+
+        list.extend(vec![4, 5]);
+        assert_eq!(list.pop(), Some(5));
+        assert_eq!(list.pop(), Some(4));
+        assert_eq!(list.pop(), Some(3));
+
+        let default: List<i32> = Default::default();
+        assert_eq!(default.peek(), None);
+    }
+
+    #[test]
+    fn into_iterator() {
+        let mut list: List<i32> = (1..=3).collect();
+
+        for elem in &mut list {
+            *elem *= 10;
+        }
+
+        let mut seen = Vec::new();
+        for elem in &list {
+            seen.push(*elem);
+        }
+        assert_eq!(seen, vec![30, 20, 10]);
+
+        let owned: Vec<i32> = list.into_iter().collect();
+        assert_eq!(owned, vec![30, 20, 10]);
+    }
+
+    #[test]
+    fn debug() {
+        let list: List<i32> = (1..=3).collect();
+        assert_eq!(format!("{:?}", list), "[3, 2, 1]");
+
+        let empty: List<i32> = List::new();
+        assert_eq!(format!("{:?}", empty), "[]");
+    }
+
+    #[test]
+    fn clone() {
+        let list: List<String> = ["a", "b", "c"].iter().map(|s| s.to_string()).collect();
+        let mut cloned = list.clone();
+        assert_eq!(list, cloned);
+
+        // the clone owns its own nodes
+        cloned.push(String::from("d"));
+        assert_ne!(list, cloned);
+        assert_eq!(cloned.pop(), Some(String::from("d")));
+        assert_eq!(cloned.pop(), Some(String::from("c")));
+        assert_eq!(list.peek(), Some(&String::from("c")));
+    }
+
+    #[test]
+    fn compare() {
+        let a: List<i32> = vec![1, 2, 3].into_iter().collect();
+        let b: List<i32> = vec![1, 2, 3].into_iter().collect();
+        let c: List<i32> = vec![1, 2, 4].into_iter().collect();
+        let d: List<i32> = vec![2, 3].into_iter().collect();
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        // comparisons go from the top of the stack downwards: [3, 2, 1] < [4, 2, 1]
+        assert!(a < c);
+        // [3, 2] is a prefix of [3, 2, 1]
+        assert!(d < a);
+        assert_eq!(a.cmp(&b), std::cmp::Ordering::Equal);
+    }
+
+    #[test]
+    fn hash() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        fn hash_of<T: Hash>(value: &T) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            value.hash(&mut hasher);
+            hasher.finish()
+        }
+
+        let a: List<i32> = (1..=3).collect();
+        let b: List<i32> = (1..=3).collect();
+        let c: List<i32> = (1..=4).collect();
+        assert_eq!(hash_of(&a), hash_of(&b));
+        assert_ne!(hash_of(&a), hash_of(&c));
+    }
+
+    #[test]
+    fn len() {
+        let mut list = List::new();
+        assert_eq!(list.len(), 0);
+        assert!(list.is_empty());
+
+        list.push(1); list.push(2); list.push(3);
+        assert_eq!(list.len(), 3);
+        assert!(!list.is_empty());
+
+        list.pop();
+        assert_eq!(list.len(), 2);
+
+        list.extend(vec![4, 5, 6]);
+        assert_eq!(list.len(), 5);
+        assert_eq!(list.clone().len(), 5);
+
+        while list.pop().is_some() {}
+        assert_eq!(list.len(), 0);
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn exact_size() {
+        let mut list: List<i32> = (1..=3).collect();
+
+        let mut iter = list.iter();
+        assert_eq!(iter.len(), 3);
+        iter.next();
+        assert_eq!(iter.size_hint(), (2, Some(2)));
+        iter.next(); iter.next();
+        assert_eq!(iter.len(), 0);
+        iter.next();
+        assert_eq!(iter.len(), 0);
+
+        let mut iter = list.iter_mut();
+        assert_eq!(iter.len(), 3);
+        iter.next();
+        assert_eq!(iter.len(), 2);
+
+        let mut iter = list.into_iter();
+        assert_eq!(iter.len(), 3);
+        iter.next();
+        assert_eq!(iter.size_hint(), (2, Some(2)));
+    }
+
+    #[test]
+    fn variance_and_auto_traits() {
+        use crate::second::{IntoIter, Iter, IterMut};
+        use crate::test_util::{assert_covariant, assert_send_sync};
+
+        // IterMut can't be covariant, see its docs
+        assert_covariant!(List<T>, Iter<'i, T>, IntoIter<T>);
+        assert_send_sync::<List<i32>>();
+        assert_send_sync::<IntoIter<i32>>();
+        assert_send_sync::<Iter<'_, i32>>();
+        assert_send_sync::<IterMut<'_, i32>>();
+    }
+
+    #[test]
+    fn long_drop() {
+        crate::test_util::drop_on_small_stack(|| {
+            (0..crate::test_util::LONG).collect::<List<_>>()
+        });
+    }
+
+    #[test]
+    fn no_leaks() {
+        use crate::test_util::{assert_no_leaks, DropTracker};
+
+        let tracker = DropTracker::new(8);
+        assert_no_leaks(|| {
+            let mut list: List<_> = (0..4).map(|id| tracker.counter(id)).collect();
+            assert_eq!(list.pop().map(|elem| elem.id()), Some(3));
+            assert_eq!(list.peek().map(|elem| elem.id()), Some(2));
+            assert_eq!(list.iter().count(), 3);
+            for elem in list.iter_mut() {
+                let _ = elem.id();
+            }
+            list.extend((4..6).map(|id| tracker.counter(id)));
+
+            // an IntoIter dropped halfway through
+            let mut iter: crate::second::IntoIter<_> = list.into_iter();
+            iter.next();
+            drop(iter);
+
+            let other: List<_> = (6..8).map(|id| tracker.counter(id)).collect();
+            let _ = other.into_iter().count();
+        });
+        tracker.assert_all_dropped_once();
+    }
+}
\ No newline at end of file
@@ -0,0 +1,703 @@
+// Chapter 7 - A Production-Quality Unsafe Doubly-Linked Deque
+
+/* 
+    `fourth` showed us the limits of Rc<RefCell>: guards everywhere, runtime borrow checks, and iterators that need 
+    unsafe to work at all. `fifth` taught us how to do raw pointers properly, but stopped at a singly-linked queue.
+    Time to put the two together, and write the deque std's LinkedList would be proud of.
+
+    The plan:
+        - nodes are allocated with Box, and from then on only handled through raw pointers (lesson from Chapter 6.5)
+        - the list owns the nodes, the nodes point at each other in both directions -> no Rc, no RefCell, no guards
+        - we hand out plain &T/&mut T, tied to borrows of the list, like any other collection
+    
+    Everything is `unsafe` on the inside, so everything has to be correct on the inside. 
+    The tests at the bottom are meant to be run under Miri (`cargo +nightly miri test sixth`).
+ */
+
+// Chapter 7.1 : Layout
+/* 
+    Two new tools, compared to `fifth`:
+
+    NonNull<T> is a `*mut T` that is never null. That buys us two things:
+        - Option<NonNull<T>> is the size of a pointer again (the null pointer optimization from Chapter 2)
+        - NonNull is COVARIANT over T, while *mut T is invariant. 
+
+    Variance is about when a type with a longer lifetime can be used where a shorter one is expected.
+    `&'a T` and `Box<T>` are covariant: a `Box<&'static str>` works wherever a `Box<&'a str>` is expected. 
+    Collections should behave the same way, or using them with borrowed data becomes needlessly painful.
+    `*mut T` can't promise that (writing a short-lived value through it would be unsound), so it's invariant - 
+    and a struct containing one is invariant too. NonNull is basically `*const T` under the hood, so it's covariant.
+
+    PhantomData<T> tells the compiler that we logically own values of type T (through the nodes), 
+    so that drop checking treats our list like a Box<T> or Vec<T> would be treated.
+ */
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+
+pub struct List<T> {
+    front: Link<T>,
+    back: Link<T>,
+    len: usize,
+    _boo: PhantomData<T>,
+}
+
+type Link<T> = Option<NonNull<Node<T>>>;
+
+struct Node<T> {
+    front: Link<T>,
+    back: Link<T>,
+    elem: T,
+}
+
+// Chapter 7.2 : Basics
+/* 
+    Same logic as the Rc deque, minus all the counting: 
+    every push allocates a node and hooks it up to its neighbour (or to both ends, if the list was empty),
+    every pop unhooks a node and turns it back into a Box, so it gets freed.
+    We use "front"/"back" for the links in both the list and the nodes, so the two directions can't get mixed up.
+ */
+
+impl<T> List<T> {
+    pub fn new() -> Self {
+        List { front: None, back: None, len: 0, _boo: PhantomData }
+    }
+
+    pub fn push_front(&mut self, elem: T) {
+        unsafe {
+            let new = NonNull::new_unchecked(Box::into_raw(Box::new(Node {
+                front: None,
+                back: None,
+                elem,
+            })));
+            if let Some(old) = self.front {
+                // put the new front in front of the old one
+                (*old.as_ptr()).front = Some(new);
+                (*new.as_ptr()).back = Some(old);
+            } else {
+                // empty list -> the new node is the back as well
+                self.back = Some(new);
+            }
+            self.front = Some(new);
+            self.len += 1;
+        }
+    }
+
+    pub fn push_back(&mut self, elem: T) {
+        unsafe {
+            let new = NonNull::new_unchecked(Box::into_raw(Box::new(Node {
+                back: None,
+                front: None,
+                elem,
+            })));
+            if let Some(old) = self.back {
+                (*old.as_ptr()).back = Some(new);
+                (*new.as_ptr()).front = Some(old);
+            } else {
+                self.front = Some(new);
+            }
+            self.back = Some(new);
+            self.len += 1;
+        }
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        unsafe {
+            self.front.map(|node| {
+                // take ownership back, so the node gets freed at the end of this scope
+                let boxed_node = Box::from_raw(node.as_ptr());
+                let result = boxed_node.elem;
+
+                // make the next node the new front
+                self.front = boxed_node.back;
+                if let Some(new) = self.front {
+                    // and cut its link to the node we just removed
+                    (*new.as_ptr()).front = None;
+                } else {
+                    // list is empty now, so the back has to go too
+                    self.back = None;
+                }
+
+                self.len -= 1;
+                result
+            })
+        }
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        unsafe {
+            self.back.map(|node| {
+                let boxed_node = Box::from_raw(node.as_ptr());
+                let result = boxed_node.elem;
+
+                self.back = boxed_node.front;
+                if let Some(new) = self.back {
+                    (*new.as_ptr()).back = None;
+                } else {
+                    self.front = None;
+                }
+
+                self.len -= 1;
+                result
+            })
+        }
+    }
+
+    pub fn front(&self) -> Option<&T> {
+        unsafe { self.front.map(|node| &(*node.as_ptr()).elem) }
+    }
+
+    pub fn front_mut(&mut self) -> Option<&mut T> {
+        unsafe { self.front.map(|node| &mut (*node.as_ptr()).elem) }
+    }
+
+    pub fn back(&self) -> Option<&T> {
+        unsafe { self.back.map(|node| &(*node.as_ptr()).elem) }
+    }
+
+    pub fn back_mut(&mut self) -> Option<&mut T> {
+        unsafe { self.back.map(|node| &mut (*node.as_ptr()).elem) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        while self.pop_front().is_some() {}
+    }
+}
+
+impl<T> Drop for List<T> {
+    fn drop(&mut self) {
+        // pop_front frees one node at a time -> no recursion
+        self.clear();
+    }
+}
+
+impl<T> Default for List<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Chapter 7.3 : Iteration
+/* 
+    With raw pointers, iterating from both ends at once is easy: keep a pointer to each end, and a count of 
+    what's left in between - once `len` hits 0, the two ends have met, whatever the pointers say.
+
+    The iterators don't own anything, they just borrow the list, so they need PhantomData for their lifetime:
+    Iter behaves like a `&'a T`, IterMut like a `&'a mut T`.
+ */
+
+pub struct Iter<'a, T> {
+    front: Link<T>,
+    back: Link<T>,
+    len: usize,
+    _boo: PhantomData<&'a T>,
+}
+
+pub struct IterMut<'a, T> {
+    front: Link<T>,
+    back: Link<T>,
+    len: usize,
+    _boo: PhantomData<&'a mut T>,
+}
+
+pub struct IntoIter<T> {
+    list: List<T>,
+}
+
+impl<T> List<T> {
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { front: self.front, back: self.back, len: self.len, _boo: PhantomData }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut { front: self.front, back: self.back, len: self.len, _boo: PhantomData }
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // the pointers never go null before len hits 0, but checking len is what stops us from crossing the back
+        if self.len > 0 {
+            self.front.map(|node| unsafe {
+                self.len -= 1;
+                self.front = (*node.as_ptr()).back;
+                &(*node.as_ptr()).elem
+            })
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> DoubleEndedIterator for Iter<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len > 0 {
+            self.back.map(|node| unsafe {
+                self.len -= 1;
+                self.back = (*node.as_ptr()).front;
+                &(*node.as_ptr()).elem
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len > 0 {
+            self.front.map(|node| unsafe {
+                self.len -= 1;
+                self.front = (*node.as_ptr()).back;
+                &mut (*node.as_ptr()).elem
+            })
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> DoubleEndedIterator for IterMut<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len > 0 {
+            self.back.map(|node| unsafe {
+                self.len -= 1;
+                self.back = (*node.as_ptr()).front;
+                &mut (*node.as_ptr()).elem
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl<T> ExactSizeIterator for IterMut<'_, T> {}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.list.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.list.len, Some(self.list.len))
+    }
+}
+
+impl<T> DoubleEndedIterator for IntoIter<T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.list.pop_back()
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+impl<T> IntoIterator for List<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter { list: self }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a List<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut List<T> {
+    type Item = &'a mut T;
+    type IntoIter = IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+// Chapter 7.4 : Boring Combinatorics - all the standard traits
+/* 
+    Everything std's LinkedList implements, built on top of push_back and the iterators.
+    Unlike `second`, this is a deque: collect/extend push to the BACK, so the order is preserved everywhere.
+ */
+
+impl<T> Extend<T> for List<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for elem in iter {
+            self.push_back(elem);
+        }
+    }
+}
+
+impl<T> FromIterator<T> for List<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = List::new();
+        list.extend(iter);
+        list
+    }
+}
+
+impl<T: Clone> Clone for List<T> {
+    fn clone(&self) -> Self {
+        self.iter().cloned().collect()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for List<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self).finish()
+    }
+}
+
+impl<T: PartialEq> PartialEq for List<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other)
+    }
+}
+
+impl<T: Eq> Eq for List<T> {}
+
+impl<T: PartialOrd> PartialOrd for List<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other)
+    }
+}
+
+impl<T: Ord> Ord for List<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other)
+    }
+}
+
+impl<T: Hash> Hash for List<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len);
+        for elem in self {
+            elem.hash(state);
+        }
+    }
+}
+
+// Chapter 7.5 : Send and Sync
+/* 
+    Raw pointers are neither Send nor Sync, so the compiler conservatively assumes our list isn't either.
+    But we own our nodes exactly like a Box would, so we're exactly as thread-safe as T is:
+    sending the list sends the T's, sharing the list shares the T's.
+    The iterators are just fancy references into the list, so the same goes for them.
+ */
+
+unsafe impl<T: Send> Send for List<T> {}
+unsafe impl<T: Sync> Sync for List<T> {}
+
+unsafe impl<T: Sync> Send for Iter<'_, T> {}
+unsafe impl<T: Sync> Sync for Iter<'_, T> {}
+
+unsafe impl<T: Send> Send for IterMut<'_, T> {}
+unsafe impl<T: Sync> Sync for IterMut<'_, T> {}
+
+#[cfg(test)]
+mod test {
+    use crate::sixth::List;
+
+    fn zero_to_six() -> List<i32> {
+        list_from(&[0, 1, 2, 3, 4, 5, 6])
+    }
+
+    fn list_from<T: Clone>(v: &[T]) -> List<T> {
+        v.iter().cloned().collect()
+    }
+
+    #[test]
+    fn basics_front() {
+        let mut list = List::new();
+
+        // try to break an empty list
+        assert_eq!(list.len(), 0);
+        assert_eq!(list.pop_front(), None);
+        assert_eq!(list.len(), 0);
+
+        // try to break a one item list
+        list.push_front(10);
+        assert_eq!(list.len(), 1);
+        assert_eq!(list.pop_front(), Some(10));
+        assert_eq!(list.len(), 0);
+        assert_eq!(list.pop_front(), None);
+        assert_eq!(list.len(), 0);
+
+        // mess around
+        list.push_front(10);
+        assert_eq!(list.len(), 1);
+        list.push_front(20);
+        assert_eq!(list.len(), 2);
+        list.push_front(30);
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.pop_front(), Some(30));
+        assert_eq!(list.len(), 2);
+        list.push_front(40);
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.pop_front(), Some(40));
+        assert_eq!(list.len(), 2);
+        assert_eq!(list.pop_front(), Some(20));
+        assert_eq!(list.len(), 1);
+        assert_eq!(list.pop_front(), Some(10));
+        assert_eq!(list.len(), 0);
+        assert_eq!(list.pop_front(), None);
+        assert_eq!(list.len(), 0);
+        assert_eq!(list.pop_front(), None);
+        assert_eq!(list.len(), 0);
+    }
+
+    #[test]
+    fn basics_back() {
+        let mut list = List::new();
+        assert_eq!(list.pop_back(), None);
+
+        list.push_back(10);
+        assert_eq!(list.pop_back(), Some(10));
+        assert_eq!(list.pop_back(), None);
+
+        list.push_back(10); list.push_back(20); list.push_back(30);
+        assert_eq!(list.pop_back(), Some(30));
+        list.push_back(40);
+        assert_eq!(list.pop_back(), Some(40));
+        // mix the two ends
+        assert_eq!(list.pop_front(), Some(10));
+        assert_eq!(list.pop_back(), Some(20));
+        assert_eq!(list.pop_front(), None);
+        assert_eq!(list.pop_back(), None);
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn peek() {
+        let mut list = List::new();
+        assert_eq!(list.front(), None);
+        assert_eq!(list.back_mut(), None);
+
+        list.push_back(1); list.push_back(2); list.push_back(3);
+        assert_eq!(list.front(), Some(&1));
+        assert_eq!(list.back(), Some(&3));
+        *list.front_mut().unwrap() = 10;
+        *list.back_mut().unwrap() *= 10;
+        assert_eq!(list.pop_front(), Some(10));
+        assert_eq!(list.pop_back(), Some(30));
+        assert_eq!(list.front(), list.back());
+    }
+
+    #[test]
+    fn iter() {
+        let list = zero_to_six();
+        for (i, elem) in list.iter().enumerate() {
+            assert_eq!(i as i32, *elem);
+        }
+
+        let mut iter = list.iter();
+        assert_eq!(iter.len(), 7);
+        assert_eq!(iter.next(), Some(&0));
+        assert_eq!(iter.next_back(), Some(&6));
+        assert_eq!(iter.len(), 5);
+        assert_eq!(iter.rev().copied().collect::<Vec<_>>(), vec![5, 4, 3, 2, 1]);
+
+        // the ends meet in the middle
+        let mut iter = list.iter();
+        for _ in 0..3 {
+            iter.next();
+            iter.next_back();
+        }
+        assert_eq!(iter.next(), Some(&3));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next_back(), None);
+
+        let empty: List<i32> = List::new();
+        assert_eq!(empty.iter().next(), None);
+    }
+
+    #[test]
+    fn iter_mut() {
+        let mut list = zero_to_six();
+        for elem in list.iter_mut() {
+            *elem *= 10;
+        }
+
+        let mut iter = list.iter_mut();
+        let first = iter.next().unwrap();
+        let last = iter.next_back().unwrap();
+        // both ends at the same time, as plain &mut
+        *first += 1;
+        *last += 1;
+        assert_eq!(iter.len(), 5);
+        assert_eq!(list.front(), Some(&1));
+        assert_eq!(list.back(), Some(&61));
+    }
+
+    #[test]
+    fn into_iter() {
+        let list = zero_to_six();
+        let mut iter = list.into_iter();
+        assert_eq!(iter.len(), 7);
+        assert_eq!(iter.next(), Some(0));
+        assert_eq!(iter.next_back(), Some(6));
+        assert_eq!(iter.collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+
+        let mut collected = Vec::new();
+        for elem in zero_to_six() {
+            collected.push(elem);
+        }
+        assert_eq!(collected, (0..7).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn eq() {
+        let mut n: List<u8> = list_from(&[]);
+        let mut m = list_from(&[]);
+        assert!(n == m);
+        n.push_front(1);
+        assert!(n != m);
+        m.push_back(1);
+        assert!(n == m);
+
+        let n = list_from(&[2, 3, 4]);
+        let m = list_from(&[1, 2, 3]);
+        assert!(n != m);
+    }
+
+    #[test]
+    fn ord() {
+        let n = list_from(&[]);
+        let m = list_from(&[1, 2, 3]);
+        assert!(n < m);
+        assert!(m > n);
+        assert!(n <= n);
+        assert!(n >= n);
+        assert!(list_from(&[1, 2]) < list_from(&[1, 3]));
+        assert_eq!(list_from(&[1, 2]).cmp(&list_from(&[1, 2])), std::cmp::Ordering::Equal);
+    }
+
+    #[test]
+    fn ord_nan() {
+        // NaN compares to nothing, so neither does a list containing it
+        let n = list_from(&[f64::NAN]);
+        let m = list_from(&[f64::NAN]);
+        assert_eq!(n.partial_cmp(&m), None);
+        assert_eq!(n.partial_cmp(&list_from(&[1.0])), None);
+        // ...unless the lists differ before reaching it
+        assert_eq!(list_from(&[0.0, f64::NAN]).partial_cmp(&list_from(&[1.0])), Some(std::cmp::Ordering::Less));
+    }
+
+    #[test]
+    fn debug() {
+        let list: List<i32> = (0..10).collect();
+        assert_eq!(format!("{:?}", list), "[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]");
+
+        let list: List<&str> = ["just", "one", "test", "more"].into_iter().collect();
+        assert_eq!(format!("{:?}", list), r#"["just", "one", "test", "more"]"#);
+    }
+
+    #[test]
+    fn hashmap() {
+        // check that the hash is consistent with equality
+        let list1: List<i32> = (0..10).collect();
+        let list2: List<i32> = (1..11).collect();
+        let mut map = std::collections::HashMap::new();
+
+        assert_eq!(map.insert(list1.clone(), "list1"), None);
+        assert_eq!(map.insert(list2.clone(), "list2"), None);
+        assert_eq!(map.len(), 2);
+
+        assert_eq!(map.get(&list1), Some(&"list1"));
+        assert_eq!(map.get(&list2), Some(&"list2"));
+        assert_eq!(map.remove(&list1), Some("list1"));
+        assert_eq!(map.remove(&list2), Some("list2"));
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn clone_extend() {
+        let mut list = zero_to_six();
+        let copy = list.clone();
+        list.extend(7..10);
+        assert_eq!(copy, zero_to_six());
+        assert_eq!(list.len(), 10);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), (0..10).collect::<Vec<_>>());
+        list.clear();
+        assert!(list.is_empty());
+        assert_eq!(list, List::default());
+    }
+
+    #[test]
+    fn variance_and_auto_traits() {
+        use crate::sixth::{IntoIter, Iter, IterMut};
+        use crate::test_util::{assert_covariant, assert_send_sync};
+
+        assert_covariant!(List<T>, Iter<'i, T>, IntoIter<T>);
+        assert_send_sync::<List<i32>>();
+        assert_send_sync::<Iter<'_, i32>>();
+        assert_send_sync::<IterMut<'_, i32>>();
+        assert_send_sync::<IntoIter<i32>>();
+
+        let list: List<i32> = (0..100).collect();
+        let sum = std::thread::spawn(move || list.iter().sum::<i32>()).join().unwrap();
+        assert_eq!(sum, 4950);
+    }
+
+    #[test]
+    fn long_drop() {
+        crate::test_util::drop_on_small_stack(|| {
+            (0..crate::test_util::LONG).collect::<List<_>>()
+        });
+    }
+
+    #[test]
+    fn no_leaks() {
+        use crate::test_util::{assert_no_leaks, DropTracker};
+
+        let tracker = DropTracker::new(8);
+        assert_no_leaks(|| {
+            let mut list: List<_> = (0..6).map(|id| tracker.counter(id)).collect();
+            assert_eq!(list.pop_front().map(|elem| elem.id()), Some(0));
+            assert_eq!(list.pop_back().map(|elem| elem.id()), Some(5));
+            assert_eq!(list.iter().count(), 4);
+            assert_eq!(list.iter_mut().rev().count(), 4);
+
+            let mut iter = list.into_iter();
+            iter.next();
+            iter.next_back();
+            drop(iter);
+
+            let mut list = List::new();
+            list.push_front(tracker.counter(6));
+            list.push_back(tracker.counter(7));
+        });
+        tracker.assert_all_dropped_once();
+    }
+}
@@ -0,0 +1,204 @@
+// Shared helpers for the tests of all the lists:
+// a tiny-stack thread for drop tests, an allocation-counting allocator, drop-counting elements, and random numbers
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+// deliberately tiny: a Drop that recurses once per node is dead long before the end of a multi-million element list
+const SMALL_STACK: usize = 128 * 1024;
+
+// how long the lists in the drop tests get.
+// Miri interprets every single node, and would take hours: there, long_drop only checks the Drop for UB, not for depth
+pub const LONG: usize = if cfg!(miri) { 2_000 } else { 2_000_000 };
+
+/* 
+    Builds a list with `build`, and drops it on a thread with a tiny stack.
+    
+    Both happen on that thread, since the Rc-based lists can't be sent anywhere.
+    Building a list never recurses, so only the drop can blow the stack.
+    A stack overflow can't be caught, it takes down the whole test binary - which fails the test run just as well.
+ */
+pub fn drop_on_small_stack<L, F>(build: F)
+where
+    F: FnOnce() -> L + Send + 'static,
+{
+    thread::Builder::new()
+        .stack_size(SMALL_STACK)
+        .spawn(move || {
+            let list = build();
+            drop(list);
+        })
+        .expect("failed to spawn the small-stack thread")
+        .join()
+        .expect("building or dropping the list panicked");
+}
+
+
+/* 
+    Leak checking, part 1: counting allocations.
+
+    Every test binary gets this allocator, which just forwards to the system allocator 
+    and keeps count of the live allocations - per thread, since the test harness runs tests in parallel.
+    `assert_no_leaks` then checks that a piece of code frees every single allocation it makes,
+    which catches leaked nodes (think Rc cycles) even when the elements themselves look fine.
+ */
+
+struct CountingAllocator;
+
+thread_local! {
+    // const-initialized and without a destructor, so it is safe to touch from inside the allocator
+    static LIVE_ALLOCATIONS: Cell<isize> = const { Cell::new(0) };
+}
+
+fn count_allocations(delta: isize) {
+    // during thread teardown the counter may already be gone; those allocations don't belong to any test
+    let _ = LIVE_ALLOCATIONS.try_with(|live| live.set(live.get() + delta));
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            count_allocations(1);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        count_allocations(-1);
+    }
+
+    // moving an allocation around doesn't change how many there are
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+// runs `f`, and fails if it leaves any allocation made on this thread behind
+pub fn assert_no_leaks<F: FnOnce()>(f: F) {
+    let before = LIVE_ALLOCATIONS.with(Cell::get);
+    f();
+    let after = LIVE_ALLOCATIONS.with(Cell::get);
+    assert_eq!(after - before, 0, "{} allocation(s) leaked", after - before);
+}
+
+/* 
+    Leak checking, part 2: counting drops.
+
+    A `DropTracker` hands out `DropCounter` elements with ids 0..n, and counts how often each one gets dropped.
+    At the end of a test, every element must have been dropped exactly once: 
+    zero means it leaked, two means a double drop (and a very bad day).
+    The counts are atomics behind an Arc, so the counters also work for the thread-safe lists.
+ */
+
+pub struct DropTracker {
+    drops: Arc<[AtomicUsize]>,
+}
+
+#[derive(Debug)]
+pub struct DropCounter {
+    id: usize,
+    drops: Arc<[AtomicUsize]>,
+}
+
+impl DropTracker {
+    pub fn new(len: usize) -> Self {
+        DropTracker { drops: (0..len).map(|_| AtomicUsize::new(0)).collect() }
+    }
+
+    pub fn counter(&self, id: usize) -> DropCounter {
+        DropCounter { id, drops: Arc::clone(&self.drops) }
+    }
+
+    pub fn drops(&self, id: usize) -> usize {
+        self.drops[id].load(Ordering::SeqCst)
+    }
+
+    pub fn assert_all_dropped_once(&self) {
+        for id in 0..self.drops.len() {
+            assert_eq!(self.drops(id), 1, "element {} was dropped {} time(s)", id, self.drops(id));
+        }
+    }
+}
+
+impl DropCounter {
+    pub fn id(&self) -> usize {
+        self.id
+    }
+}
+
+impl PartialEq for DropCounter {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Drop for DropCounter {
+    fn drop(&mut self) {
+        self.drops[self.id].fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/* 
+    A xorshift pseudo-random generator (Marsaglia, "Xorshift RNGs"), for the tests that throw random operations at a list.
+    Not remotely good enough for anything else - but it's tiny, needs no crate, 
+    and the same seed always gives the same sequence, so every failure is reproducible.
+ */
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // xorshift gets stuck on 0
+        Rng(seed | 1)
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+/* 
+    Compile-time checks for variance and auto traits: none of this does anything at runtime,
+    it either compiles or it doesn't.
+
+    `assert_send_sync::<List<i32>>()` only compiles if the type is both Send and Sync.
+
+    `assert_covariant!(List<T>, Iter<'i, T>)` writes, for every type, a little identity function 
+    that shortens the lifetime inside each type parameter: `List<&'static str> -> List<&'a str>`.
+    The compiler only accepts it if the type is covariant over them (the Nomicon, "Subtyping and Variance").
+
+    Why each list is (or isn't) Send, Sync and covariant is explained next to the list itself;
+    the types that must NOT be get a compile_fail doctest there instead, since a test can't check that something fails to compile.
+ */
+
+pub fn assert_send_sync<T: Send + Sync>() {}
+
+macro_rules! assert_covariant {
+    ($($($segment:ident)::+ < $($lt:lifetime ,)? $($param:ident),+ >),+ $(,)?) => {$(
+        const _: () = {
+            #[allow(dead_code)]
+            fn shorten<$($lt,)? 'a>(
+                long: $($segment)::+ < $($lt,)? $($crate::test_util::str_ref!('static, $param)),+ >,
+            ) -> $($segment)::+ < $($lt,)? $($crate::test_util::str_ref!('a, $param)),+ > {
+                long
+            }
+        };
+    )+};
+}
+
+// stands in for one type parameter of the type being checked
+macro_rules! str_ref {
+    ($lt:lifetime, $param:ident) => { &$lt str };
+}
+
+pub(crate) use {assert_covariant, str_ref};
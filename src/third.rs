@@ -31,12 +31,32 @@ How to do this in Rust!? -> Reference Counting --> Rc
 
 use std::rc::Rc;
 
+/// Covariant over `T`, but never `Send` or `Sync`, whatever `T` is: 
+/// its nodes are shared through non-atomic `Rc`s. Use `third::sync::List` to cross threads.
+///
+/// ```compile_fail
+/// use tutorial_too_many_lists::third::List;
+///
+/// let list = List::new().prepend(1);
+/// std::thread::spawn(move || drop(list));
+/// ```
+///
+/// ```compile_fail
+/// use tutorial_too_many_lists::third::List;
+///
+/// let list = List::new().prepend(1);
+/// std::thread::scope(|s| { s.spawn(|| list.head().copied()); });
+/// ```
 pub struct List<T> {
     head: Link<T>,
+    // every list version carries its own length; prepend/tail just derive it from the old one
+    len: usize,
 }
 
 type Link<T> = Option<Rc<Node<T>>>;
 
+// Clone only copies the element; `next` is an Rc, so the rest of the list is shared (see Chapter 4.6)
+#[derive(Clone)]
 struct Node<T> {
     elem: T, 
     next: Link<T>,
@@ -47,7 +67,7 @@ struct Node<T> {
 
 impl<T> List<T> {
     pub fn new() -> Self {
-        List { head: None }
+        List { head: None, len: 0 }
     }
 
     // Replace `push` and `pop`  with  `prepend` and `tail`
@@ -58,20 +78,24 @@ impl<T> List<T> {
     pub fn prepend(&self, elem: T) -> List<T> {
         List { head: Some(Rc::new(
             Node {
-                elem: elem, 
+                elem, 
                 next: self.head.clone(),
                 // Clone is implemented for almost every type; 
                 // Rc uses Clone as a way to increment its reference count
                 //  --> we don't move a box to a sublist, but instead we clone the head of the old list
                 // no matching of the head needed, Option exposes a Clone implementation for us <3
             }
-        )) }
+        )), len: self.len + 1 }
     }
 
     // tail is the logical inverse -> takes a list and returns it with the first element removed
     pub fn tail(&self) -> List<T> {
         // make use of the `and_then` pattern for Options -> lets us return an Option
-        List { head: self.head.as_ref().and_then(|node| node.next.clone()) }
+        List { 
+            head: self.head.as_ref().and_then(|node| node.next.clone()),
+            // the tail of the empty list is the empty list
+            len: self.len.saturating_sub(1),
+        }
     }
     
     // in addition to tail, we also need a way to get the head, to return a reference to the current first element
@@ -80,9 +104,43 @@ impl<T> List<T> {
         self.head.as_ref().map(|node| &node.elem)
     }
 
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+}
+
+impl<T> Default for List<T> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 // Iter is the same as it was for the mutable list from Chapter 3
+/// Covariant over `T`, but just like the list neither `Send` nor `Sync`: 
+/// sharing a `&Node` would share the `Rc` inside it, and `Rc` isn't `Sync`.
+///
+/// ```compile_fail
+/// use tutorial_too_many_lists::third::List;
+///
+/// fn assert_send<T: Send>(_: &T) {}
+/// let list = List::new().prepend(1);
+/// let iter = list.iter();
+/// assert_send(&iter);
+/// ```
+///
+/// ```compile_fail
+/// use tutorial_too_many_lists::third::List;
+///
+/// fn assert_sync<T: Sync>(_: &T) {}
+/// let list = List::new().prepend(1);
+/// let iter = list.iter();
+/// assert_sync(&iter);
+/// ```
 pub struct Iter<'a, T> {
     next: Option<&'a Node<T>>,
 }
@@ -116,18 +174,22 @@ impl<'a, T> Iterator for Iter<'a, T> {
     Like the mutable list, we have a recursive destructor problem; but not as bad. 
     If we ever hit another node that's the head of another list, somewhere, wo will NOT recursively drop it. 
     Still, it is a thing we should care about, but it is not immediately clear how to deal with it. 
+
+    Turns out the loop below already does: a shared node is never dropped here, but its last owner is another list,
+    and whenever THAT list gets dropped, its own loop picks up right where ours stopped. 
+    So no matter how long the lists are, or how they share their tails, no drop ever recurses along a list.
 */
 impl<T> Drop for List<T> {
     fn drop(&mut self) {
-        // take the original head, and move it into scope to implicitely drop it later 
-        let mut head = self.head.take();
+        // take the original head, and move it into a guard (see LeakOnUnwind below) to drop it later
+        let mut rest = LeakOnUnwind(self.head.take());
         // then, while we actually have valid nodes
-        while let Some(node) = head {
+        while let Some(node) = rest.0.take() {
             // try unwrap the value at the Rc pointer location
             if let Ok(mut node) = Rc::try_unwrap(node) {
                 // if we are the LAST watcher of the value behind the Rc, it lets us take the value;
                 // we then move it into scope, and that's the last we'll ever hear of it. 
-                head = node.next.take();
+                rest.0 = node.next.take();
             } else {
                 // if anyone else is still watching the Rc, we leave them alone and go on a nice long
                 break;
@@ -136,6 +198,26 @@ impl<T> Drop for List<T> {
     }
 }
 
+/* 
+    If an element's drop panics, unwinding drops whatever is still on our stack - including the rest of the chain,
+    which would then go down through Rc's own, recursive drop glue. On a long list, that's the very stack overflow
+    we worked so hard to avoid, only now in the middle of a panic. 
+    So the rest of the chain sits in this guard while elements are dropped. The loop always empties it,
+    so the only way it still holds something when it's dropped is unwinding - and then we leak instead.
+
+    Note what this does NOT cover: elements that own lists of their own (think `List<Tree>` where every Tree holds a `List<Tree>`).
+    Dropping such an element runs a whole new loop one level deeper on the stack, so deep enough nesting still overflows.
+    Handing those inner lists to the outermost loop instead (say, through a thread-local queue) would be unsound:
+    they'd get dropped after the element's drop returned, when whatever their elements borrow may be long gone.
+ */
+struct LeakOnUnwind<L>(Option<L>);
+
+impl<L> Drop for LeakOnUnwind<L> {
+    fn drop(&mut self) {
+        std::mem::forget(self.0.take());
+    }
+}
+
 /*  Chapter 4.4 - Arc
 
     Immutable linked lists are awesome to make data available across threads, BUT our implementation is unsafe due to shared mutable state.
@@ -150,6 +232,346 @@ impl<T> Drop for List<T> {
     Of course, you can't magically make a type thread safe by putting it in Arc. Arc can only derive thread-safety like any other type.
 */
 
+// The persistent deque built out of two of these lists lives in its own file: third/deque.rs
+pub mod deque;
+// ... and so does the persistent ordered map, which shares subtrees instead of list tails: third/map.rs
+pub mod map;
+
+// So here it is: the very same persistent list, with Rc swapped for Arc.
+// It lives in its own module, so it can keep the familiar names: `third::sync::List` vs `third::List`
+// (just like std has `rc::Rc` and `sync::Arc`).
+// Send + Sync are derived automatically: Arc<Node<T>> is Send + Sync exactly when T is.
+pub mod sync {
+    use std::sync::Arc;
+
+    /// Covariant over `T`. Since nodes are shared between lists (and threads), 
+    /// sending a list also shares its elements: it's only `Send`/`Sync` when `T` is both.
+    ///
+    /// ```compile_fail
+    /// // Cell is Send, but not Sync
+    /// use std::cell::Cell;
+    /// use tutorial_too_many_lists::third::sync::List;
+    ///
+    /// let list = List::new().prepend(Cell::new(1));
+    /// std::thread::spawn(move || drop(list));
+    /// ```
+    pub struct List<T> {
+        head: Link<T>,
+        len: usize,
+    }
+
+    type Link<T> = Option<Arc<Node<T>>>;
+
+    struct Node<T> {
+        elem: T,
+        next: Link<T>,
+    }
+
+    impl<T> List<T> {
+        pub fn new() -> Self {
+            List { head: None, len: 0 }
+        }
+
+        pub fn prepend(&self, elem: T) -> List<T> {
+            List {
+                head: Some(Arc::new(Node { elem, next: self.head.clone() })),
+                len: self.len + 1,
+            }
+        }
+
+        pub fn tail(&self) -> List<T> {
+            List {
+                head: self.head.as_ref().and_then(|node| node.next.clone()),
+                len: self.len.saturating_sub(1),
+            }
+        }
+
+        pub fn head(&self) -> Option<&T> {
+            self.head.as_ref().map(|node| &node.elem)
+        }
+
+        pub fn len(&self) -> usize {
+            self.len
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.len == 0
+        }
+
+        pub fn iter(&self) -> Iter<'_, T> {
+            Iter { next: self.head.as_deref() }
+        }
+    }
+
+    impl<T> Default for List<T> {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    // cloning a list only bumps the (atomic) count of its head node
+    impl<T> Clone for List<T> {
+        fn clone(&self) -> Self {
+            List { head: self.head.clone(), len: self.len }
+        }
+    }
+
+    pub struct Iter<'a, T> {
+        next: Option<&'a Node<T>>,
+    }
+
+    impl<'a, T> Iterator for Iter<'a, T> {
+        type Item = &'a T;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            self.next.map(|node| {
+                self.next = node.next.as_deref();
+                &node.elem
+            })
+        }
+    }
+
+    // same as Chapter 4.3, with Arc instead of Rc:
+    // stop as soon as another list (possibly on another thread) still holds on to a node
+    impl<T> Drop for List<T> {
+        fn drop(&mut self) {
+            // leaked on unwind, see super::LeakOnUnwind
+            let mut rest = super::LeakOnUnwind(self.head.take());
+            while let Some(node) = rest.0.take() {
+                // NOT try_unwrap: if two threads drop their lists at the same time, both try_unwraps can fail,
+                // and whoever lets go of their Arc last drops the node through Arc's own (recursive) drop glue.
+                // into_inner takes the node out for whoever is the last owner, atomically.
+                if let Some(mut node) = Arc::into_inner(node) {
+                    // store the rest BEFORE the node (and its element) gets dropped
+                    rest.0 = node.next.take();
+                }
+            }
+        }
+    }
+}
+
+
+// Chapter 4.5 : Functional combinators
+/* 
+    Persistent lists are the bread and butter of functional languages, so let's give ours the usual toolbox.
+    Every operation returns a NEW list and leaves self alone, and we share as much structure as we can:
+        - drop_n, tail: share everything that is left
+        - append: has to copy the left list (its last node must point somewhere new), but shares all of the right one
+        - filter: shares the longest suffix in which every element passes, copies the rest
+        - reverse, map, take, zip: every node is different, so everything gets copied
+
+    Copying needs T: Clone, since we can never move elements out of shared nodes.
+    Most of them collect the new elements into a Vec first and then prepend them back-to-front, because prepend is all we've got.
+
+    `ptr_eq` and `shares_tail_with` let us actually see the sharing in the tests.
+ */
+
+// cloning a list only bumps the count of its head node -> O(1), no elements get copied
+impl<T> Clone for List<T> {
+    fn clone(&self) -> Self {
+        List { head: self.head.clone(), len: self.len }
+    }
+}
+
+impl<T> List<T> {
+    // prepends `elems` (front-to-back order) onto `tail`, sharing all of `tail`
+    fn prepend_all<I>(tail: List<T>, elems: I) -> List<T>
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: DoubleEndedIterator,
+    {
+        elems.into_iter().rev().fold(tail, |list, elem| list.prepend(elem))
+    }
+
+    pub fn nth(&self, n: usize) -> Option<&T> {
+        self.iter().nth(n)
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        self.iter().last()
+    }
+
+    pub fn contains(&self, elem: &T) -> bool
+    where
+        T: PartialEq,
+    {
+        self.iter().any(|e| e == elem)
+    }
+
+    pub fn fold<B, F>(&self, init: B, f: F) -> B
+    where
+        F: FnMut(B, &T) -> B,
+    {
+        self.iter().fold(init, f)
+    }
+
+    // the list without its first n elements; shares all of them with self
+    pub fn drop_n(&self, n: usize) -> List<T> {
+        let mut head = self.head.as_ref();
+        for _ in 0..n {
+            head = head.and_then(|node| node.next.as_ref());
+        }
+        List { head: head.cloned(), len: self.len.saturating_sub(n) }
+    }
+
+    // the first n elements; copies them, unless the whole list is taken
+    pub fn take(&self, n: usize) -> List<T>
+    where
+        T: Clone,
+    {
+        if n >= self.len {
+            return self.clone();
+        }
+        let elems: Vec<T> = self.iter().take(n).cloned().collect();
+        Self::prepend_all(List::new(), elems)
+    }
+
+    pub fn reverse(&self) -> List<T>
+    where
+        T: Clone,
+    {
+        self.fold(List::new(), |list, elem| list.prepend(elem.clone()))
+    }
+
+    // self followed by other; copies self, shares all of other
+    pub fn append(&self, other: &List<T>) -> List<T>
+    where
+        T: Clone,
+    {
+        let elems: Vec<T> = self.iter().cloned().collect();
+        Self::prepend_all(other.clone(), elems)
+    }
+
+    pub fn map<U, F>(&self, f: F) -> List<U>
+    where
+        F: FnMut(&T) -> U,
+    {
+        let elems: Vec<U> = self.iter().map(f).collect();
+        List::prepend_all(List::new(), elems)
+    }
+
+    // keeps the elements matching the predicate; shares the longest all-matching suffix with self
+    pub fn filter<P>(&self, mut predicate: P) -> List<T>
+    where
+        T: Clone,
+        P: FnMut(&T) -> bool,
+    {
+        let mut kept: Vec<&T> = Vec::new();
+        // how many of `kept` sit in front of the last rejected element -> these need copying
+        let mut copied = 0;
+        // index right after the last rejected element -> everything from here on is shared
+        let mut shared_from = 0;
+        for (index, elem) in self.iter().enumerate() {
+            if predicate(elem) {
+                kept.push(elem);
+            } else {
+                copied = kept.len();
+                shared_from = index + 1;
+            }
+        }
+        let elems: Vec<T> = kept[..copied].iter().map(|elem| (*elem).clone()).collect();
+        Self::prepend_all(self.drop_n(shared_from), elems)
+    }
+
+    // pairs up the elements of both lists, stopping at the end of the shorter one
+    pub fn zip<U>(&self, other: &List<U>) -> List<(T, U)>
+    where
+        T: Clone,
+        U: Clone,
+    {
+        let elems: Vec<(T, U)> = self.iter().cloned().zip(other.iter().cloned()).collect();
+        List::prepend_all(List::new(), elems)
+    }
+
+    // true if both lists are the very same version (same head node, or both empty)
+    pub fn ptr_eq(&self, other: &List<T>) -> bool {
+        match (&self.head, &other.head) {
+            (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+
+    // true if the lists share at least one node. 
+    // Sharing only ever happens at the end of a list, so it is enough to compare the last nodes.
+    pub fn shares_tail_with(&self, other: &List<T>) -> bool {
+        fn last_node<T>(list: &List<T>) -> Option<&Rc<Node<T>>> {
+            let mut node = list.head.as_ref()?;
+            while let Some(next) = node.next.as_ref() {
+                node = next;
+            }
+            Some(node)
+        }
+        match (last_node(self), last_node(other)) {
+            (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+// Chapter 4.6 : Copy-on-write
+/* 
+    Back in Chapter 4 we said we can never take data out of the list, nor mutate it. That is only half true:
+    if WE are the only one looking at a node, nobody can tell whether we mutate it or not!
+    
+    `Rc::try_unwrap` (which Drop already uses) hands us the node when we are its only owner.
+    Its sibling `Rc::make_mut` goes one step further: it hands out a `&mut` to the node, 
+    cloning the node first if anyone else is still looking at it. 
+    Cloning a node only copies its element - `next` is just another Rc - so everything behind it stays shared.
+
+    So we get mutation for free on unshared nodes, and path copying (only the nodes up to the edit) on shared ones.
+    Other list versions never see any of it.
+ */
+
+impl<T> List<T> {
+    // the head element, mutable; copies the head node first if it is shared
+    pub fn head_mut(&mut self) -> Option<&mut T>
+    where
+        T: Clone,
+    {
+        self.head.as_mut().map(|node| &mut Rc::make_mut(node).elem)
+    }
+
+    // removes the head element and hands it out by value: 
+    // moved out if we were the head node's only owner, cloned otherwise
+    pub fn pop_owned(&mut self) -> Option<T>
+    where
+        T: Clone,
+    {
+        self.head.take().map(|node| {
+            self.len -= 1;
+            match Rc::try_unwrap(node) {
+                Ok(node) => {
+                    self.head = node.next;
+                    node.elem
+                }
+                Err(node) => {
+                    self.head = node.next.clone();
+                    node.elem.clone()
+                }
+            }
+        })
+    }
+
+    // runs `f` on the element at `index`, copying only the shared nodes on the way there.
+    // Returns None (and copies nothing) if index is out of bounds.
+    pub fn update<R, F>(&mut self, index: usize, f: F) -> Option<R>
+    where
+        T: Clone,
+        F: FnOnce(&mut T) -> R,
+    {
+        if index >= self.len {
+            return None;
+        }
+        let mut link = &mut self.head;
+        for _ in 0..index {
+            // index < len, so there is always a next node
+            link = &mut Rc::make_mut(link.as_mut()?).next;
+        }
+        link.as_mut().map(|node| f(&mut Rc::make_mut(node).elem))
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::third::List;
@@ -188,4 +610,432 @@ mod test {
         assert_eq!(iter.next(), None);
     }
 
+
+    #[test]
+    fn len() {
+        let empty = List::new();
+        assert_eq!(empty.len(), 0);
+        assert!(empty.is_empty());
+
+        let list = empty.prepend(1).prepend(2).prepend(3);
+        assert_eq!(list.len(), 3);
+        assert!(!list.is_empty());
+
+        // every version keeps its own length, shared nodes or not
+        let shorter = list.tail();
+        let other = shorter.prepend(42);
+        assert_eq!(shorter.len(), 2);
+        assert_eq!(other.len(), 3);
+        assert_eq!(list.len(), 3);
+
+        let gone = shorter.tail().tail();
+        assert_eq!(gone.len(), 0);
+        assert_eq!(gone.tail().len(), 0);
+        assert!(gone.tail().is_empty());
+    }
+
+    #[test]
+    fn sync_basics() {
+        use crate::third::sync::List;
+
+        let list = List::new();
+        assert_eq!(list.head(), None);
+
+        let list = list.prepend(1).prepend(2).prepend(3);
+        assert_eq!(list.head(), Some(&3));
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![3, 2, 1]);
+
+        let list = list.tail();
+        assert_eq!(list.head(), Some(&2));
+        let list = list.tail().tail();
+        assert_eq!(list.head(), None);
+        assert!(list.tail().is_empty());
+    }
+
+    #[test]
+    fn sync_threads() {
+        use crate::third::sync::List;
+        use std::thread;
+
+        crate::test_util::assert_send_sync::<List<i32>>();
+
+        let shared = List::new().prepend(1).prepend(2).prepend(3);
+
+        // every thread builds its own version on top of the shared tail
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let shared = shared.clone();
+                thread::spawn(move || {
+                    let mine = shared.prepend(i * 10);
+                    assert_eq!(mine.len(), 4);
+                    mine.iter().sum::<i32>()
+                })
+            })
+            .collect();
+
+        for (i, handle) in handles.into_iter().enumerate() {
+            assert_eq!(handle.join().unwrap(), i as i32 * 10 + 6);
+        }
+
+        // all the threads' versions are gone, ours is untouched
+        assert_eq!(shared.iter().copied().collect::<Vec<_>>(), vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn queries() {
+        let list = List::new().prepend(3).prepend(2).prepend(1);
+
+        assert_eq!(list.nth(0), Some(&1));
+        assert_eq!(list.nth(2), Some(&3));
+        assert_eq!(list.nth(3), None);
+        assert_eq!(list.last(), Some(&3));
+        assert_eq!(List::<i32>::new().last(), None);
+        assert!(list.contains(&2));
+        assert!(!list.contains(&4));
+        assert_eq!(list.fold(0, |acc, elem| acc * 10 + elem), 123);
+    }
+
+    #[test]
+    fn sharing() {
+        let base = List::new().prepend(3).prepend(2);
+        let a = base.prepend(1);
+        let b = base.prepend(10);
+        let other = List::new().prepend(2).prepend(1);
+
+        assert!(a.tail().ptr_eq(&b.tail()));
+        assert!(!a.ptr_eq(&b));
+        assert!(a.clone().ptr_eq(&a));
+        assert!(List::<i32>::new().ptr_eq(&List::new()));
+
+        assert!(a.shares_tail_with(&b));
+        assert!(a.shares_tail_with(&base));
+        assert!(!a.shares_tail_with(&other));
+        assert!(!a.shares_tail_with(&List::new()));
+    }
+
+    #[test]
+    fn take_and_drop() {
+        let list = List::new().prepend(4).prepend(3).prepend(2).prepend(1);
+
+        let dropped = list.drop_n(2);
+        assert_eq!(dropped.iter().copied().collect::<Vec<_>>(), vec![3, 4]);
+        assert_eq!(dropped.len(), 2);
+        assert!(dropped.ptr_eq(&list.tail().tail()));
+        assert!(list.drop_n(10).is_empty());
+
+        let taken = list.take(2);
+        assert_eq!(taken.iter().copied().collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(taken.len(), 2);
+        assert!(!taken.shares_tail_with(&list));
+        // taking everything is just another handle on the same list
+        assert!(list.take(4).ptr_eq(&list));
+        assert!(list.take(0).is_empty());
+    }
+
+    #[test]
+    fn reverse_map_zip() {
+        let list = List::new().prepend(3).prepend(2).prepend(1);
+
+        let reversed = list.reverse();
+        assert_eq!(reversed.iter().copied().collect::<Vec<_>>(), vec![3, 2, 1]);
+
+        let mapped = list.map(|elem| elem.to_string());
+        assert_eq!(mapped.iter().cloned().collect::<Vec<_>>(), vec!["1", "2", "3"]);
+        assert_eq!(mapped.len(), 3);
+
+        let zipped = list.zip(&mapped.tail());
+        assert_eq!(
+            zipped.iter().cloned().collect::<Vec<_>>(),
+            vec![(1, String::from("2")), (2, String::from("3"))]
+        );
+        assert_eq!(zipped.len(), 2);
+
+        // the original is untouched by all of this
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn append() {
+        let left = List::new().prepend(2).prepend(1);
+        let right = List::new().prepend(4).prepend(3);
+
+        let both = left.append(&right);
+        assert_eq!(both.iter().copied().collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+        assert_eq!(both.len(), 4);
+        // the right list is shared as a whole, the left one got copied
+        assert!(both.drop_n(2).ptr_eq(&right));
+        assert!(!both.shares_tail_with(&left));
+
+        assert!(List::new().append(&right).ptr_eq(&right));
+        assert_eq!(left.append(&List::new()).iter().copied().collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[test]
+    fn filter() {
+        let list = List::new().prepend(6).prepend(4).prepend(3).prepend(2).prepend(1);
+
+        let even = list.filter(|elem| elem % 2 == 0);
+        assert_eq!(even.iter().copied().collect::<Vec<_>>(), vec![2, 4, 6]);
+        assert_eq!(even.len(), 3);
+        // [4, 6] comes after the last odd element, so it is shared
+        assert!(even.tail().ptr_eq(&list.drop_n(3)));
+
+        let all = list.filter(|_| true);
+        assert!(all.ptr_eq(&list));
+
+        let none = list.filter(|_| false);
+        assert!(none.is_empty());
+
+        // the predicate runs exactly once per element
+        let mut calls = 0;
+        list.filter(|_| { calls += 1; true });
+        assert_eq!(calls, 5);
+    }
+
+    #[test]
+    fn head_mut() {
+        let mut unique = List::new().prepend(2).prepend(1);
+        let before = unique.tail();
+        *unique.head_mut().unwrap() = 10;
+        assert_eq!(unique.head(), Some(&10));
+        // only the head node was ever touched
+        assert!(unique.tail().ptr_eq(&before));
+
+        let original = List::new().prepend(2).prepend(1);
+        let mut copy = original.clone();
+        *copy.head_mut().unwrap() = 10;
+        // the shared head got copied, the original is untouched
+        assert_eq!(original.head(), Some(&1));
+        assert_eq!(copy.head(), Some(&10));
+        assert!(copy.tail().ptr_eq(&original.tail()));
+
+        assert_eq!(List::<i32>::new().head_mut(), None);
+    }
+
+    #[test]
+    fn pop_owned() {
+        use std::rc::Rc;
+
+        // unique head: the element is moved out
+        let elem = Rc::new(1);
+        let mut list = List::new().prepend(Rc::clone(&elem));
+        assert_eq!(Rc::strong_count(&elem), 2);
+        let popped = list.pop_owned().unwrap();
+        assert_eq!(Rc::strong_count(&elem), 2);
+        drop(popped);
+        assert_eq!(Rc::strong_count(&elem), 1);
+        assert!(list.is_empty());
+
+        // shared head: the element is cloned, the other version keeps its own
+        let shared = List::new().prepend(Rc::clone(&elem)).prepend(Rc::new(0));
+        let mut mine = shared.tail();
+        let popped = mine.pop_owned().unwrap();
+        assert_eq!(Rc::strong_count(&elem), 3);
+        assert!(Rc::ptr_eq(&popped, &elem));
+        assert!(mine.is_empty());
+        assert_eq!(shared.len(), 2);
+        assert_eq!(mine.pop_owned(), None);
+        assert_eq!(mine.len(), 0);
+    }
+
+    #[test]
+    fn update() {
+        let original = List::new().prepend(4).prepend(3).prepend(2).prepend(1);
+        let mut edited = original.clone();
+
+        assert_eq!(edited.update(2, |elem| { *elem *= 10; *elem }), Some(30));
+        assert_eq!(edited.iter().copied().collect::<Vec<_>>(), vec![1, 2, 30, 4]);
+        assert_eq!(original.iter().copied().collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+        // only the path up to the edit got copied, the rest is still shared
+        assert!(edited.drop_n(3).ptr_eq(&original.drop_n(3)));
+        assert!(!edited.drop_n(2).ptr_eq(&original.drop_n(2)));
+
+        // edits on our own (now unshared) path don't copy again
+        let before = edited.drop_n(1);
+        edited.update(0, |elem| *elem = 100);
+        assert!(edited.drop_n(1).ptr_eq(&before));
+        drop(before);
+
+        // out of bounds: nothing happens
+        let snapshot = edited.clone();
+        assert_eq!(edited.update(4, |elem| *elem = 0), None);
+        assert!(edited.ptr_eq(&snapshot));
+    }
+
+    #[test]
+    fn long_branching_drop() {
+        // a million shared nodes, with lots of long-ish branches hanging off of it
+        let mut base = List::new();
+        for elem in 0..1_000_000 {
+            base = base.prepend(elem);
+        }
+        let mut branches = Vec::new();
+        let mut fork = base.clone();
+        for branch in 0..100 {
+            let mut list = fork.clone();
+            for elem in 0..1_000 {
+                list = list.prepend(branch * 1_000 + elem);
+            }
+            branches.push(list);
+            fork = fork.drop_n(10_000);
+        }
+
+        // drop the base first, so the branches end up owning its nodes
+        drop(base);
+        drop(fork);
+        // then drop the branches front-to-back, every one of them releasing a 10_000 element unshared stretch
+        for branch in branches {
+            assert_eq!(branch.len() % 1_000, 0);
+            drop(branch);
+        }
+    }
+
+    #[test]
+    fn nested_drop_with_borrows() {
+        use crate::third::sync;
+
+        // reads what it borrows when dropped
+        struct Loud<'a>(&'a String);
+        impl Drop for Loud<'_> {
+            fn drop(&mut self) {
+                assert_eq!(self.0, "local");
+            }
+        }
+
+        // drops a list borrowing one of its locals, while the outer list is busy dropping it:
+        // the inner list has to be gone before `drop` returns and the local goes away
+        struct Scoped;
+        impl Drop for Scoped {
+            fn drop(&mut self) {
+                let local = String::from("local");
+                drop(List::new().prepend(Loud(&local)).prepend(Loud(&local)));
+                drop(sync::List::new().prepend(Loud(&local)));
+            }
+        }
+
+        drop(List::new().prepend(Scoped).prepend(Scoped));
+        drop(sync::List::new().prepend(Scoped).prepend(Scoped));
+    }
+
+    #[test]
+    fn drop_after_panic() {
+        use std::panic;
+
+        struct Bomb;
+        impl Drop for Bomb {
+            fn drop(&mut self) {
+                panic!("boom");
+            }
+        }
+
+        let result = panic::catch_unwind(|| {
+            let list = List::new().prepend(Bomb);
+            drop(list);
+        });
+        assert!(result.is_err());
+
+        // nothing got stuck: dropping keeps working on this thread
+        let list = List::new().prepend(1).prepend(2);
+        drop(list);
+
+        // a panic at the head of a long list must not drop the rest recursively while unwinding
+        struct Mine(bool);
+        impl Drop for Mine {
+            fn drop(&mut self) {
+                if self.0 {
+                    panic!("boom");
+                }
+            }
+        }
+
+        // catches the panic of dropping its list, so that drop_on_small_stack only fails on a stack overflow
+        struct CatchDrop<L>(Option<L>);
+        impl<L> Drop for CatchDrop<L> {
+            fn drop(&mut self) {
+                let list = self.0.take();
+                assert!(panic::catch_unwind(panic::AssertUnwindSafe(|| drop(list))).is_err());
+            }
+        }
+
+        crate::test_util::drop_on_small_stack(|| {
+            let mut list = List::new();
+            for _ in 0..crate::test_util::LONG {
+                list = list.prepend(Mine(false));
+            }
+            CatchDrop(Some(list.prepend(Mine(true))))
+        });
+        crate::test_util::drop_on_small_stack(|| {
+            let mut list = crate::third::sync::List::new();
+            for _ in 0..crate::test_util::LONG {
+                list = list.prepend(Mine(false));
+            }
+            CatchDrop(Some(list.prepend(Mine(true))))
+        });
+    }
+
+    #[test]
+    fn variance_and_auto_traits() {
+        use crate::third::{deque, map, sync, Iter};
+        use crate::test_util::{assert_covariant, assert_send_sync};
+
+        assert_covariant!(
+            List<T>, Iter<'i, T>,
+            sync::List<T>, sync::Iter<'i, T>,
+            deque::Deque<T>, deque::Iter<'i, T>,
+            map::Map<K, V>, map::Iter<'i, K, V>, map::Range<'i, K, V>,
+        );
+
+        // only the Arc flavour crosses threads (the Rc ones can't, see the compile_fail docs)
+        assert_send_sync::<sync::List<i32>>();
+        assert_send_sync::<sync::Iter<'_, i32>>();
+    }
+
+    #[test]
+    fn long_drop() {
+        crate::test_util::drop_on_small_stack(|| {
+            let mut list = List::new();
+            for elem in 0..crate::test_util::LONG {
+                list = list.prepend(elem);
+            }
+            list
+        });
+        // same again, but with a second version holding on to most of the list until the very end
+        crate::test_util::drop_on_small_stack(|| {
+            let mut list = List::new();
+            for elem in 0..crate::test_util::LONG {
+                list = list.prepend(elem);
+            }
+            let branch = list.drop_n(10).prepend(42);
+            drop(list);
+            branch
+        });
+    }
+
+    #[test]
+    fn no_leaks() {
+        use crate::test_util::{assert_no_leaks, DropTracker};
+
+        let tracker = DropTracker::new(6);
+        assert_no_leaks(|| {
+            let base = List::new()
+                .prepend(tracker.counter(0))
+                .prepend(tracker.counter(1))
+                .prepend(tracker.counter(2));
+            let branch = base.tail().prepend(tracker.counter(3));
+            let other = branch.tail().tail().prepend(tracker.counter(4));
+            assert_eq!(base.iter().count(), 3);
+            drop(base);
+            // 2 was only in base, 1 and 0 are still shared
+            assert_eq!(tracker.drops(2), 1);
+            assert_eq!(tracker.drops(1), 0);
+            drop(branch);
+            let mut owned = other.prepend(tracker.counter(5));
+            drop(other);
+            assert_eq!(owned.head().map(|elem| elem.id()), Some(5));
+            owned = owned.tail();
+            assert_eq!(owned.len(), 2);
+        });
+        tracker.assert_all_dropped_once();
+    }
 }
\ No newline at end of file
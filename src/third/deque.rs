@@ -0,0 +1,305 @@
+// Chapter 4.7 - A persistent deque, out of two persistent lists
+
+/* 
+    `third::List` is great at the front and useless at the back. The classic functional fix (Okasaki's "banker's deque"):
+    keep TWO lists, one for each end.
+
+        front: A -> B -> C          (front-to-back order)
+        back:  F -> E -> D          (back-to-front order, i.e. reversed)
+        deque: A B C D E F
+
+    Pushing/popping at either end is just prepend/tail on the matching list -> O(1), fully shared with older versions.
+    When one of the lists runs (nearly) dry, we rebalance: split the elements evenly and reverse half of them over. 
+    To keep that rare, we maintain the invariant
+        front.len() <= C * back.len() + 1   and   back.len() <= C * front.len() + 1
+    A rebalance of n elements only happens after ~n/C cheap operations, so the end operations are amortized O(1).
+
+    Fine print: the amortized O(1) bound does NOT hold once old versions get reused.
+    Someone can keep re-popping the same version that's one step away from a rebalance, 
+    and pay the full O(n) rebalance every single time. Okasaki fixes that with lazy evaluation; we don't have that, 
+    so the O(1) bound only holds for the usual "keep using the newest version" style.
+
+    Rebalancing copies elements (we can't move them out of shared nodes), so everything that may rebalance needs T: Clone.
+    Just like `List`, every operation takes &self and returns a new version; old versions are never touched.
+ */
+
+use super::List;
+
+// how unbalanced the two lists may get before we rebalance
+const C: usize = 3;
+
+/// Covariant over `T`, and (being made of `third::List`s) neither `Send` nor `Sync`.
+///
+/// ```compile_fail
+/// use tutorial_too_many_lists::third::deque::Deque;
+///
+/// let deque = Deque::new().push_back(1);
+/// std::thread::spawn(move || drop(deque));
+/// ```
+pub struct Deque<T> {
+    front: List<T>,
+    back: List<T>,
+}
+
+impl<T> Deque<T> {
+    pub fn new() -> Self {
+        Deque { front: List::new(), back: List::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.front.len() + self.back.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // thanks to the invariant, an empty front list means there is at most one element, which sits in back (and vice versa)
+    pub fn front(&self) -> Option<&T> {
+        self.front.head().or_else(|| self.back.head())
+    }
+
+    pub fn back(&self) -> Option<&T> {
+        self.back.head().or_else(|| self.front.head())
+    }
+
+    // O(1) to create, and free as long as it stays in the front list.
+    // The back list is stored reversed, so the first `next` that reaches it has to collect it to walk it the other way around:
+    // O(len(back)) time and memory, once per iterator.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { front: self.front.iter(), back: &self.back, reversed: None }
+    }
+}
+
+impl<T: Clone> Deque<T> {
+    // restores the invariant, rebalancing if one of the lists got too long
+    fn balanced(front: List<T>, back: List<T>) -> Self {
+        let (front_len, back_len) = (front.len(), back.len());
+        let half = (front_len + back_len) / 2;
+        if front_len > C * back_len + 1 {
+            // keep the first half in front, reverse the rest onto the end of back
+            Deque {
+                front: front.take(half),
+                back: back.append(&front.drop_n(half).reverse()),
+            }
+        } else if back_len > C * front_len + 1 {
+            Deque {
+                front: front.append(&back.drop_n(half).reverse()),
+                back: back.take(half),
+            }
+        } else {
+            Deque { front, back }
+        }
+    }
+
+    pub fn push_front(&self, elem: T) -> Deque<T> {
+        Self::balanced(self.front.prepend(elem), self.back.clone())
+    }
+
+    pub fn push_back(&self, elem: T) -> Deque<T> {
+        Self::balanced(self.front.clone(), self.back.prepend(elem))
+    }
+
+    // the deque without its front element
+    pub fn pop_front(&self) -> Deque<T> {
+        if self.front.is_empty() {
+            // at most one element left, and it is the one we pop
+            return Deque::new();
+        }
+        Self::balanced(self.front.tail(), self.back.clone())
+    }
+
+    // the deque without its back element
+    pub fn pop_back(&self) -> Deque<T> {
+        if self.back.is_empty() {
+            return Deque::new();
+        }
+        Self::balanced(self.front.clone(), self.back.tail())
+    }
+
+    // self followed by other. O(len(self) + len(other)): 
+    // self is flattened into one front list, other into one back list, sharing other's back list as-is
+    pub fn concat(&self, other: &Deque<T>) -> Deque<T> {
+        let front = self.front.append(&self.back.reverse());
+        let back = other.back.append(&other.front.reverse());
+        Self::balanced(front, back)
+    }
+}
+
+impl<T> Default for Deque<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// O(1), just like List's clone
+impl<T> Clone for Deque<T> {
+    fn clone(&self) -> Self {
+        Deque { front: self.front.clone(), back: self.back.clone() }
+    }
+}
+
+pub struct Iter<'a, T> {
+    front: super::Iter<'a, T>,
+    back: &'a List<T>,
+    // the back list in front-to-back order, built when the front list runs out
+    reversed: Option<std::iter::Rev<std::vec::IntoIter<&'a T>>>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(elem) = self.front.next() {
+            return Some(elem);
+        }
+        let back = self.back;
+        self.reversed.get_or_insert_with(|| back.iter().collect::<Vec<_>>().into_iter().rev()).next()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test_util::Rng;
+    use crate::third::deque::Deque;
+    use std::collections::VecDeque;
+
+    fn elems(deque: &Deque<i32>) -> Vec<i32> {
+        deque.iter().copied().collect()
+    }
+
+    #[test]
+    fn basics() {
+        let deque = Deque::new();
+        assert_eq!(deque.front(), None);
+        assert_eq!(deque.back(), None);
+        assert!(deque.pop_front().is_empty());
+        assert!(deque.pop_back().is_empty());
+
+        let deque = deque.push_back(2).push_back(3).push_front(1);
+        assert_eq!(deque.front(), Some(&1));
+        assert_eq!(deque.back(), Some(&3));
+        assert_eq!(deque.len(), 3);
+        assert_eq!(elems(&deque), vec![1, 2, 3]);
+
+        let deque = deque.pop_front();
+        assert_eq!(deque.front(), Some(&2));
+        let deque = deque.pop_back();
+        assert_eq!(deque.back(), Some(&2));
+        assert_eq!(deque.front(), Some(&2));
+        let deque = deque.pop_back();
+        assert!(deque.is_empty());
+        assert_eq!(deque.front(), None);
+
+        // a single element pushed to one end is visible from the other end too
+        let deque = Deque::new().push_front(1);
+        assert_eq!(deque.back(), Some(&1));
+        assert!(deque.pop_back().is_empty());
+        let deque = Deque::new().push_back(1);
+        assert_eq!(deque.front(), Some(&1));
+        assert!(deque.pop_front().is_empty());
+    }
+
+    #[test]
+    fn iter() {
+        // after the rebalance: 1 in the front list, 4 3 2 in the back list
+        let deque = Deque::new().push_front(2).push_front(1).push_back(3).push_back(4);
+        let mut iter = deque.iter();
+        assert_eq!(iter.next(), Some(&1));
+        // only now does the back list get walked, in the right order
+        assert_eq!(iter.next(), Some(&2));
+        assert_eq!(iter.next(), Some(&3));
+        assert_eq!(iter.next(), Some(&4));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next(), None);
+
+        assert_eq!(Deque::<i32>::new().iter().next(), None);
+        assert_eq!(elems(&Deque::new().push_back(1).push_back(2)), vec![1, 2]);
+    }
+
+    #[test]
+    fn one_sided() {
+        // only ever pushing to the back, and popping from the front: has to rebalance along the way
+        let mut deque = Deque::new();
+        for elem in 0..100 {
+            deque = deque.push_back(elem);
+        }
+        for elem in 0..100 {
+            assert_eq!(deque.front(), Some(&elem));
+            deque = deque.pop_front();
+        }
+        assert!(deque.is_empty());
+
+        for elem in 0..100 {
+            deque = deque.push_front(elem);
+        }
+        for elem in 0..100 {
+            assert_eq!(deque.back(), Some(&elem));
+            deque = deque.pop_back();
+        }
+        assert!(deque.is_empty());
+    }
+
+    #[test]
+    fn against_vec_deque() {
+        // a simple deterministic pseudo-random op sequence, compared against std's VecDeque
+        let mut rng = Rng::new(0x2545_f491);
+        let mut deque = Deque::new();
+        let mut model = VecDeque::new();
+        for step in 0..2000 {
+            match rng.next() % 4 {
+                0 => { deque = deque.push_front(step); model.push_front(step); }
+                1 => { deque = deque.push_back(step); model.push_back(step); }
+                2 => { deque = deque.pop_front(); model.pop_front(); }
+                _ => { deque = deque.pop_back(); model.pop_back(); }
+            }
+            assert_eq!(deque.front(), model.front());
+            assert_eq!(deque.back(), model.back());
+            assert_eq!(deque.len(), model.len());
+        }
+        assert_eq!(elems(&deque), model.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn persistence() {
+        let base = Deque::new().push_back(1).push_back(2).push_back(3);
+        let more = base.push_front(0).push_back(4);
+        let less = base.pop_front().pop_back();
+
+        assert_eq!(elems(&base), vec![1, 2, 3]);
+        assert_eq!(elems(&more), vec![0, 1, 2, 3, 4]);
+        assert_eq!(elems(&less), vec![2]);
+
+        // pushing to the front never touches the back list, and vice versa
+        let front_pushed = more.push_front(-1);
+        assert!(front_pushed.back.ptr_eq(&more.back));
+        let back_pushed = more.push_back(5);
+        assert!(back_pushed.front.ptr_eq(&more.front));
+    }
+
+    #[test]
+    fn concat() {
+        let left = Deque::new().push_back(1).push_back(2).push_front(0);
+        let right = Deque::new().push_back(4).push_back(5).push_front(3);
+
+        let both = left.concat(&right);
+        assert_eq!(elems(&both), vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(both.len(), 6);
+        assert_eq!(both.front(), Some(&0));
+        assert_eq!(both.back(), Some(&5));
+
+        // the inputs are untouched
+        assert_eq!(elems(&left), vec![0, 1, 2]);
+        assert_eq!(elems(&right), vec![3, 4, 5]);
+
+        assert_eq!(elems(&Deque::new().concat(&right)), vec![3, 4, 5]);
+        assert_eq!(elems(&left.concat(&Deque::new())), vec![0, 1, 2]);
+        let mut lopsided = Deque::new();
+        for elem in 0..20 {
+            lopsided = lopsided.push_back(elem);
+        }
+        let joined = Deque::new().push_back(-1).concat(&lopsided);
+        assert_eq!(elems(&joined), (-1..20).collect::<Vec<_>>());
+        assert_eq!(joined.pop_back().pop_back().back(), Some(&17));
+    }
+}
@@ -0,0 +1,427 @@
+// Chapter 4.8 - A persistent ordered map, out of shared tree nodes
+
+/* 
+    Structural sharing works for trees just as well as for lists - even better, actually.
+    A list can only share its tail, but a search tree can share every subtree an update doesn't walk through.
+
+    Updating a key only rebuilds the path from the root down to that key (path copying):
+
+        old root ---> D                  D' <--- new root
+                     / \                / \
+                    B   F              B   F'
+                   / \ / \                / \
+                  A  C E  G              E   G'
+
+    Updating G creates new copies of G, F and D (primed); B (with A and C) and E are shared by both versions.
+
+    To keep the paths short, the tree is an AVL tree: the heights of the two subtrees of any node differ by at most 1,
+    which keeps the height (and therefore the cost of get/insert/remove) at O(log n).
+
+    Nodes are never mutated once built, so rebalancing doesn't rotate nodes in place - it builds new ones.
+    That is also why keys and values need Clone: the nodes on the copied path get new copies of them.
+
+    Drop is left to Rc: dropping a tree recurses at most once per level, and a balanced tree is only O(log n) deep.
+ */
+
+use std::cmp::Ordering;
+use std::ops::{Bound, RangeBounds};
+use std::rc::Rc;
+
+/// Covariant over `K` and `V`; its subtrees are shared through `Rc`s, so it's neither `Send` nor `Sync`.
+///
+/// ```compile_fail
+/// use tutorial_too_many_lists::third::map::Map;
+///
+/// let map = Map::new().insert(1, "one");
+/// std::thread::spawn(move || drop(map));
+/// ```
+pub struct Map<K, V> {
+    root: Tree<K, V>,
+    len: usize,
+}
+
+type Tree<K, V> = Option<Rc<Node<K, V>>>;
+
+struct Node<K, V> {
+    key: K,
+    value: V,
+    left: Tree<K, V>,
+    right: Tree<K, V>,
+    height: usize,
+}
+
+fn height<K, V>(tree: &Tree<K, V>) -> usize {
+    tree.as_ref().map_or(0, |node| node.height)
+}
+
+// builds a node on top of two (already balanced) subtrees
+fn node<K, V>(key: K, value: V, left: Tree<K, V>, right: Tree<K, V>) -> Rc<Node<K, V>> {
+    let height = 1 + height(&left).max(height(&right));
+    Rc::new(Node { key, value, left, right, height })
+}
+
+// builds a node on top of two subtrees whose heights differ by at most 2, rotating to restore the AVL invariant
+fn balance<K: Clone, V: Clone>(key: K, value: V, left: Tree<K, V>, right: Tree<K, V>) -> Rc<Node<K, V>> {
+    let (left_height, right_height) = (height(&left), height(&right));
+    if left_height > right_height + 1 {
+        let l = left.expect("left subtree is higher, so it exists");
+        if height(&l.left) >= height(&l.right) {
+            // single rotation to the right
+            let new_right = node(key, value, l.right.clone(), right);
+            node(l.key.clone(), l.value.clone(), l.left.clone(), Some(new_right))
+        } else {
+            // double rotation: left-right
+            let lr = l.right.as_ref().expect("inner grandchild is higher, so it exists");
+            let new_left = node(l.key.clone(), l.value.clone(), l.left.clone(), lr.left.clone());
+            let new_right = node(key, value, lr.right.clone(), right);
+            node(lr.key.clone(), lr.value.clone(), Some(new_left), Some(new_right))
+        }
+    } else if right_height > left_height + 1 {
+        let r = right.expect("right subtree is higher, so it exists");
+        if height(&r.right) >= height(&r.left) {
+            // single rotation to the left
+            let new_left = node(key, value, left, r.left.clone());
+            node(r.key.clone(), r.value.clone(), Some(new_left), r.right.clone())
+        } else {
+            // double rotation: right-left
+            let rl = r.left.as_ref().expect("inner grandchild is higher, so it exists");
+            let new_left = node(key, value, left, rl.left.clone());
+            let new_right = node(r.key.clone(), r.value.clone(), rl.right.clone(), r.right.clone());
+            node(rl.key.clone(), rl.value.clone(), Some(new_left), Some(new_right))
+        }
+    } else {
+        node(key, value, left, right)
+    }
+}
+
+// returns the new tree, and whether the key was new
+fn insert<K: Ord + Clone, V: Clone>(tree: &Tree<K, V>, key: K, value: V) -> (Rc<Node<K, V>>, bool) {
+    match tree {
+        None => (node(key, value, None, None), true),
+        Some(n) => match key.cmp(&n.key) {
+            Ordering::Less => {
+                let (left, added) = insert(&n.left, key, value);
+                (balance(n.key.clone(), n.value.clone(), Some(left), n.right.clone()), added)
+            }
+            Ordering::Greater => {
+                let (right, added) = insert(&n.right, key, value);
+                (balance(n.key.clone(), n.value.clone(), n.left.clone(), Some(right)), added)
+            }
+            // same key: new value, same subtrees
+            Ordering::Equal => (node(key, value, n.left.clone(), n.right.clone()), false),
+        },
+    }
+}
+
+// removes the smallest entry of a subtree, handing it out along with the rest of the subtree
+fn remove_min<K: Clone, V: Clone>(n: &Rc<Node<K, V>>) -> (K, V, Tree<K, V>) {
+    match &n.left {
+        None => (n.key.clone(), n.value.clone(), n.right.clone()),
+        Some(left) => {
+            let (key, value, left) = remove_min(left);
+            (key, value, Some(balance(n.key.clone(), n.value.clone(), left, n.right.clone())))
+        }
+    }
+}
+
+// returns None if the key isn't there, so the caller can keep sharing the whole old tree
+fn remove<K: Ord + Clone, V: Clone>(tree: &Tree<K, V>, key: &K) -> Option<Tree<K, V>> {
+    let n = tree.as_ref()?;
+    match key.cmp(&n.key) {
+        Ordering::Less => remove(&n.left, key)
+            .map(|left| Some(balance(n.key.clone(), n.value.clone(), left, n.right.clone()))),
+        Ordering::Greater => remove(&n.right, key)
+            .map(|right| Some(balance(n.key.clone(), n.value.clone(), n.left.clone(), right))),
+        Ordering::Equal => Some(match (&n.left, &n.right) {
+            (None, right) => right.clone(),
+            (left, None) => left.clone(),
+            // two children: the smallest entry on the right takes our place
+            (left, Some(right)) => {
+                let (key, value, right) = remove_min(right);
+                Some(balance(key, value, left.clone(), right))
+            }
+        }),
+    }
+}
+
+impl<K, V> Map<K, V> {
+    pub fn new() -> Self {
+        Map { root: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V>
+    where
+        K: Ord,
+    {
+        let mut tree = &self.root;
+        while let Some(n) = tree {
+            tree = match key.cmp(&n.key) {
+                Ordering::Less => &n.left,
+                Ordering::Greater => &n.right,
+                Ordering::Equal => return Some(&n.value),
+            };
+        }
+        None
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool
+    where
+        K: Ord,
+    {
+        self.get(key).is_some()
+    }
+
+    // all entries, in key order
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        let mut iter = Iter { stack: Vec::new() };
+        iter.push_left(&self.root);
+        iter
+    }
+
+    // the entries with keys in `range`, in key order
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Range<'_, K, V>
+    where
+        K: Ord + Clone,
+    {
+        // walk down to the first key in range, stacking up every node we will still have to visit
+        let mut stack = Vec::new();
+        let mut tree = &self.root;
+        while let Some(n) = tree {
+            let after_start = match range.start_bound() {
+                Bound::Included(start) => n.key >= *start,
+                Bound::Excluded(start) => n.key > *start,
+                Bound::Unbounded => true,
+            };
+            if after_start {
+                stack.push(&**n);
+                tree = &n.left;
+            } else {
+                tree = &n.right;
+            }
+        }
+        Range { iter: Iter { stack }, end: range.end_bound().cloned() }
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> Map<K, V> {
+    // a new version with `key` mapped to `value`; shares every subtree off the path to `key`
+    pub fn insert(&self, key: K, value: V) -> Map<K, V> {
+        let (root, added) = insert(&self.root, key, value);
+        Map { root: Some(root), len: self.len + added as usize }
+    }
+
+    // a new version without `key`; if `key` isn't there, the new version IS the old one
+    pub fn remove(&self, key: &K) -> Map<K, V> {
+        match remove(&self.root, key) {
+            Some(root) => Map { root, len: self.len - 1 },
+            None => self.clone(),
+        }
+    }
+}
+
+impl<K, V> Default for Map<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// O(1): only the root gets another owner
+impl<K, V> Clone for Map<K, V> {
+    fn clone(&self) -> Self {
+        Map { root: self.root.clone(), len: self.len }
+    }
+}
+
+// in-order traversal with an explicit stack: the top of the stack is always the next entry,
+// and below it are all its ancestors we haven't visited yet
+pub struct Iter<'a, K, V> {
+    stack: Vec<&'a Node<K, V>>,
+}
+
+impl<'a, K, V> Iter<'a, K, V> {
+    fn push_left(&mut self, mut tree: &'a Tree<K, V>) {
+        while let Some(n) = tree {
+            self.stack.push(n);
+            tree = &n.left;
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.stack.pop().map(|n| {
+            self.push_left(&n.right);
+            (&n.key, &n.value)
+        })
+    }
+}
+
+pub struct Range<'a, K, V> {
+    iter: Iter<'a, K, V>,
+    end: Bound<K>,
+}
+
+impl<'a, K: Ord, V> Iterator for Range<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (key, value) = self.iter.next()?;
+        let before_end = match &self.end {
+            Bound::Included(end) => key <= end,
+            Bound::Excluded(end) => key < end,
+            Bound::Unbounded => true,
+        };
+        if before_end {
+            Some((key, value))
+        } else {
+            // everything after this is out of range as well
+            self.iter.stack.clear();
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test_util::Rng;
+    use crate::third::map::{Map, Tree};
+    use std::collections::BTreeMap;
+    use std::rc::Rc;
+
+    // checks the AVL invariant and the stored heights, returns the height
+    fn check_balanced<K, V>(tree: &Tree<K, V>) -> usize {
+        match tree {
+            None => 0,
+            Some(n) => {
+                let left = check_balanced(&n.left);
+                let right = check_balanced(&n.right);
+                assert!(left.abs_diff(right) <= 1, "AVL invariant broken");
+                assert_eq!(n.height, 1 + left.max(right));
+                n.height
+            }
+        }
+    }
+
+    #[test]
+    fn basics() {
+        let map = Map::new();
+        assert_eq!(map.get(&1), None);
+        assert!(map.is_empty());
+
+        let map = map.insert(2, "two").insert(1, "one").insert(3, "three");
+        assert_eq!(map.get(&1), Some(&"one"));
+        assert_eq!(map.get(&2), Some(&"two"));
+        assert_eq!(map.get(&3), Some(&"three"));
+        assert_eq!(map.get(&4), None);
+        assert!(map.contains_key(&3));
+        assert_eq!(map.len(), 3);
+
+        // overwriting doesn't change the length
+        let map = map.insert(2, "TWO");
+        assert_eq!(map.get(&2), Some(&"TWO"));
+        assert_eq!(map.len(), 3);
+
+        let map = map.remove(&2);
+        assert_eq!(map.get(&2), None);
+        assert_eq!(map.len(), 2);
+        let map = map.remove(&2);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.iter().collect::<Vec<_>>(), vec![(&1, &"one"), (&3, &"three")]);
+    }
+
+    #[test]
+    fn persistence() {
+        let v1 = Map::new().insert(1, 'a').insert(2, 'b');
+        let v2 = v1.insert(3, 'c');
+        let v3 = v2.remove(&1).insert(2, 'B');
+
+        assert_eq!(v1.iter().collect::<Vec<_>>(), vec![(&1, &'a'), (&2, &'b')]);
+        assert_eq!(v2.iter().collect::<Vec<_>>(), vec![(&1, &'a'), (&2, &'b'), (&3, &'c')]);
+        assert_eq!(v3.iter().collect::<Vec<_>>(), vec![(&2, &'B'), (&3, &'c')]);
+    }
+
+    #[test]
+    fn sharing() {
+        let mut map = Map::new();
+        for key in 0..15 {
+            map = map.insert(key, key);
+        }
+        let root = map.root.as_ref().unwrap();
+
+        // updating something on the right leaves the left subtree alone
+        let max = map.iter().last().map(|(key, _)| *key).unwrap();
+        let updated = map.insert(max, 100);
+        let new_root = updated.root.as_ref().unwrap();
+        assert!(!Rc::ptr_eq(root, new_root));
+        assert!(Rc::ptr_eq(root.left.as_ref().unwrap(), new_root.left.as_ref().unwrap()));
+
+        // removing a missing key shares everything
+        let same = map.remove(&1000);
+        assert!(Rc::ptr_eq(root, same.root.as_ref().unwrap()));
+    }
+
+    #[test]
+    fn against_btree_map() {
+        let mut rng = Rng::new(0x9e37_79b9);
+        let mut map = Map::new();
+        let mut model = BTreeMap::new();
+        for step in 0..3000 {
+            let random = rng.next();
+            let key = random % 200;
+            match random % 3 {
+                0 => { map = map.remove(&key); model.remove(&key); }
+                _ => { map = map.insert(key, step); model.insert(key, step); }
+            }
+            assert_eq!(map.len(), model.len());
+            assert_eq!(map.get(&key), model.get(&key));
+        }
+        check_balanced(&map.root);
+        assert!(map.iter().eq(model.iter()));
+    }
+
+    #[test]
+    fn balanced() {
+        // sorted insertion is the worst case for an unbalanced search tree
+        let mut map = Map::new();
+        for key in 0..1024 {
+            map = map.insert(key, ());
+        }
+        assert!(check_balanced(&map.root) <= 11);
+        for key in (0..1024).step_by(2) {
+            map = map.remove(&key);
+        }
+        check_balanced(&map.root);
+        assert_eq!(map.len(), 512);
+    }
+
+    #[test]
+    fn range() {
+        let mut map = Map::new();
+        for key in (0..20).step_by(2) {
+            map = map.insert(key, key * 10);
+        }
+
+        let keys = |iter: crate::third::map::Range<'_, i32, i32>| iter.map(|(key, _)| *key).collect::<Vec<_>>();
+        assert_eq!(keys(map.range(4..10)), vec![4, 6, 8]);
+        assert_eq!(keys(map.range(3..=10)), vec![4, 6, 8, 10]);
+        assert_eq!(keys(map.range(..4)), vec![0, 2]);
+        assert_eq!(keys(map.range(15..)), vec![16, 18]);
+        assert_eq!(keys(map.range(..)), (0..20).step_by(2).collect::<Vec<_>>());
+        assert_eq!(keys(map.range(7..8)), vec![]);
+        assert_eq!(keys(map.range(100..)), vec![]);
+
+        use std::ops::Bound;
+        assert_eq!(keys(map.range((Bound::Excluded(4), Bound::Excluded(10)))), vec![6, 8]);
+        assert_eq!(map.range(6..7).next(), Some((&6, &60)));
+    }
+}
@@ -0,0 +1,389 @@
+// Common vocabulary for all the lists
+
+/* 
+    Every chapter grew its own names: push/pop in `second`, prepend/tail in `third`, push_front/pop_back in `fourth`...
+    Fine for a tutorial, useless if you want to write code that works with any of them.
+    So here are the four shapes our lists come in, as traits:
+
+        Stack            - LIFO: push and pop at the same end         (first, second, fourth, sixth)
+        Queue            - FIFO: push at the back, pop at the front   (fourth, fifth, sixth)
+        Deque            - a Queue that pushes and pops at both ends  (fourth, sixth)
+        PersistentStack  - a Stack that never changes: every "update" returns a new version (third, third::sync)
+
+    Peeking is the one awkward bit: `fourth` can't hand out a plain &T, only a `Ref<T>` guard.
+    So the traits let every list pick what it hands out (`Peek`), as long as it derefs to a T.
+    
+    All the impls are down here, built on the public API only: the lists themselves don't know about any of this.
+ */
+
+use std::cell::Ref;
+use std::ops::Deref;
+
+use crate::{fifth, first, fourth, second, sixth, third};
+
+pub trait Stack<T> {
+    type Peek<'a>: Deref<Target = T> where Self: 'a;
+
+    fn push(&mut self, elem: T);
+    fn pop(&mut self) -> Option<T>;
+    fn peek(&self) -> Option<Self::Peek<'_>>;
+    fn is_empty(&self) -> bool;
+}
+
+pub trait Queue<T> {
+    type Peek<'a>: Deref<Target = T> where Self: 'a;
+
+    // push at the back
+    fn enqueue(&mut self, elem: T);
+    // pop at the front
+    fn dequeue(&mut self) -> Option<T>;
+    // the next element `dequeue` will return
+    fn front(&self) -> Option<Self::Peek<'_>>;
+    fn is_empty(&self) -> bool;
+}
+
+// a deque is a queue that also works the other way around:
+// `front`, `is_empty` and the `Peek` type are the Queue ones, so a list that is both never has two `front`s
+pub trait Deque<T>: Queue<T> {
+    fn push_front(&mut self, elem: T);
+    fn pop_back(&mut self) -> Option<T>;
+    fn back(&self) -> Option<Self::Peek<'_>>;
+
+    // the queue operations, under their deque names
+    fn push_back(&mut self, elem: T) { self.enqueue(elem) }
+    fn pop_front(&mut self) -> Option<T> { self.dequeue() }
+}
+
+// everything takes &self, and the old version stays valid (and unchanged) forever
+pub trait PersistentStack<T>: Sized {
+    fn prepend(&self, elem: T) -> Self;
+    // everything but the head; the tail of an empty stack is empty
+    fn tail(&self) -> Self;
+    fn head(&self) -> Option<&T>;
+    fn is_empty(&self) -> bool;
+}
+
+
+// Stacks
+
+impl<T> Stack<T> for first::List<T> {
+    type Peek<'a> = &'a T where Self: 'a;
+
+    fn push(&mut self, elem: T) { self.push(elem) }
+    fn pop(&mut self) -> Option<T> { self.pop() }
+    fn peek(&self) -> Option<&T> { self.peek() }
+    fn is_empty(&self) -> bool { self.is_empty() }
+}
+
+impl<T> Stack<T> for second::List<T> {
+    type Peek<'a> = &'a T where Self: 'a;
+
+    fn push(&mut self, elem: T) { self.push(elem) }
+    fn pop(&mut self) -> Option<T> { self.pop() }
+    fn peek(&self) -> Option<&T> { self.peek() }
+    fn is_empty(&self) -> bool { self.is_empty() }
+}
+
+impl<T> Stack<T> for fourth::List<T> {
+    type Peek<'a> = Ref<'a, T> where Self: 'a;
+
+    fn push(&mut self, elem: T) { self.push_front(elem) }
+    fn pop(&mut self) -> Option<T> { self.pop_front() }
+    fn peek(&self) -> Option<Ref<'_, T>> { self.peek_front() }
+    fn is_empty(&self) -> bool { self.is_empty() }
+}
+
+impl<T> Stack<T> for sixth::List<T> {
+    type Peek<'a> = &'a T where Self: 'a;
+
+    fn push(&mut self, elem: T) { self.push_front(elem) }
+    fn pop(&mut self) -> Option<T> { self.pop_front() }
+    fn peek(&self) -> Option<&T> { self.front() }
+    fn is_empty(&self) -> bool { self.is_empty() }
+}
+
+
+// Queues
+
+impl<T> Queue<T> for fourth::List<T> {
+    type Peek<'a> = Ref<'a, T> where Self: 'a;
+
+    fn enqueue(&mut self, elem: T) { self.push_back(elem) }
+    fn dequeue(&mut self) -> Option<T> { self.pop_front() }
+    fn front(&self) -> Option<Ref<'_, T>> { self.peek_front() }
+    fn is_empty(&self) -> bool { self.is_empty() }
+}
+
+impl<T> Queue<T> for fifth::List<T> {
+    type Peek<'a> = &'a T where Self: 'a;
+
+    fn enqueue(&mut self, elem: T) { self.push(elem) }
+    fn dequeue(&mut self) -> Option<T> { self.pop() }
+    fn front(&self) -> Option<&T> { self.peek() }
+    // fifth doesn't count its elements, but an empty queue has nothing to peek at
+    fn is_empty(&self) -> bool { self.peek().is_none() }
+}
+
+impl<T> Queue<T> for sixth::List<T> {
+    type Peek<'a> = &'a T where Self: 'a;
+
+    fn enqueue(&mut self, elem: T) { self.push_back(elem) }
+    fn dequeue(&mut self) -> Option<T> { self.pop_front() }
+    fn front(&self) -> Option<&T> { self.front() }
+    fn is_empty(&self) -> bool { self.is_empty() }
+}
+
+
+// Deques
+
+impl<T> Deque<T> for fourth::List<T> {
+    fn push_front(&mut self, elem: T) { self.push_front(elem) }
+    fn pop_back(&mut self) -> Option<T> { self.pop_back() }
+    fn back(&self) -> Option<Ref<'_, T>> { self.peek_back() }
+}
+
+impl<T> Deque<T> for sixth::List<T> {
+    fn push_front(&mut self, elem: T) { self.push_front(elem) }
+    fn pop_back(&mut self) -> Option<T> { self.pop_back() }
+    fn back(&self) -> Option<&T> { self.back() }
+}
+
+
+// Persistent stacks
+
+impl<T> PersistentStack<T> for third::List<T> {
+    fn prepend(&self, elem: T) -> Self { self.prepend(elem) }
+    fn tail(&self) -> Self { self.tail() }
+    fn head(&self) -> Option<&T> { self.head() }
+    fn is_empty(&self) -> bool { self.is_empty() }
+}
+
+impl<T> PersistentStack<T> for third::sync::List<T> {
+    fn prepend(&self, elem: T) -> Self { self.prepend(elem) }
+    fn tail(&self) -> Self { self.tail() }
+    fn head(&self) -> Option<&T> { self.head() }
+    fn is_empty(&self) -> bool { self.is_empty() }
+}
+
+
+/* 
+    The conformance suite: one generic test per trait, written against the trait only, 
+    and run once for every list that implements it. 
+    If a list passes, it can be swapped for any other implementation of the same trait.
+ */
+#[cfg(test)]
+mod test {
+    use crate::traits::{Deque, PersistentStack, Queue, Stack};
+    use crate::test_util::{assert_no_leaks, DropCounter, DropTracker, Rng};
+    use crate::{fifth, first, fourth, second, sixth, third};
+    use std::collections::VecDeque;
+
+    // a deterministic, but well-mixed sequence of operations
+    fn ops() -> impl Iterator<Item = u64> {
+        let mut rng = Rng::new(0x2545_f491);
+        (0..500).map(move |_| rng.next())
+    }
+
+    fn stack<S: Stack<i32> + Default>() {
+        let mut stack = S::default();
+        assert!(stack.is_empty());
+        assert!(stack.pop().is_none());
+        assert!(stack.peek().is_none());
+
+        stack.push(1); stack.push(2); stack.push(3);
+        assert!(!stack.is_empty());
+        assert_eq!(stack.peek().map(|elem| *elem), Some(3));
+        assert_eq!(stack.pop(), Some(3));
+        assert_eq!(stack.pop(), Some(2));
+        stack.push(4);
+        assert_eq!(stack.pop(), Some(4));
+        assert_eq!(stack.pop(), Some(1));
+        assert_eq!(stack.pop(), None);
+        assert!(stack.is_empty());
+
+        // against a Vec, the reference stack
+        let mut model = Vec::new();
+        for (i, op) in ops().enumerate() {
+            if op % 3 == 0 {
+                assert_eq!(stack.pop(), model.pop());
+            } else {
+                stack.push(i as i32);
+                model.push(i as i32);
+            }
+            assert_eq!(stack.peek().map(|elem| *elem), model.last().copied());
+            assert_eq!(stack.is_empty(), model.is_empty());
+        }
+    }
+
+    fn queue<Q: Queue<i32> + Default>() {
+        let mut queue = Q::default();
+        assert!(queue.is_empty());
+        assert!(queue.dequeue().is_none());
+        assert!(queue.front().is_none());
+
+        queue.enqueue(1); queue.enqueue(2); queue.enqueue(3);
+        assert!(!queue.is_empty());
+        assert_eq!(queue.front().map(|elem| *elem), Some(1));
+        assert_eq!(queue.dequeue(), Some(1));
+        assert_eq!(queue.dequeue(), Some(2));
+        queue.enqueue(4);
+        assert_eq!(queue.dequeue(), Some(3));
+        assert_eq!(queue.dequeue(), Some(4));
+        assert_eq!(queue.dequeue(), None);
+        // an emptied queue has to work just like a new one
+        queue.enqueue(5);
+        assert_eq!(queue.front().map(|elem| *elem), Some(5));
+        assert_eq!(queue.dequeue(), Some(5));
+        assert!(queue.is_empty());
+
+        let mut model = VecDeque::new();
+        for (i, op) in ops().enumerate() {
+            if op % 3 == 0 {
+                assert_eq!(queue.dequeue(), model.pop_front());
+            } else {
+                queue.enqueue(i as i32);
+                model.push_back(i as i32);
+            }
+            assert_eq!(queue.front().map(|elem| *elem), model.front().copied());
+            assert_eq!(queue.is_empty(), model.is_empty());
+        }
+    }
+
+    fn deque<D: Deque<i32> + Default>() {
+        let mut deque = D::default();
+        assert!(deque.is_empty());
+        assert!(deque.pop_front().is_none());
+        assert!(deque.pop_back().is_none());
+        assert!(deque.front().is_none());
+        assert!(deque.back().is_none());
+
+        deque.push_front(2); deque.push_back(3); deque.push_front(1);
+        assert_eq!(deque.front().map(|elem| *elem), Some(1));
+        assert_eq!(deque.back().map(|elem| *elem), Some(3));
+        assert_eq!(deque.pop_back(), Some(3));
+        assert_eq!(deque.pop_front(), Some(1));
+        // one element left: it's both the front and the back
+        assert_eq!(deque.front().map(|elem| *elem), Some(2));
+        assert_eq!(deque.back().map(|elem| *elem), Some(2));
+        assert_eq!(deque.pop_back(), Some(2));
+        assert!(deque.is_empty());
+        assert!(deque.pop_front().is_none());
+
+        let mut model = VecDeque::new();
+        for (i, op) in ops().enumerate() {
+            let i = i as i32;
+            match op % 5 {
+                0 => assert_eq!(deque.pop_front(), model.pop_front()),
+                1 => assert_eq!(deque.pop_back(), model.pop_back()),
+                2 => { deque.push_front(i); model.push_front(i); }
+                _ => { deque.push_back(i); model.push_back(i); }
+            }
+            assert_eq!(deque.front().map(|elem| *elem), model.front().copied());
+            assert_eq!(deque.back().map(|elem| *elem), model.back().copied());
+            assert_eq!(deque.is_empty(), model.is_empty());
+        }
+    }
+
+    fn persistent_stack<P: PersistentStack<i32> + Default>() {
+        let empty = P::default();
+        assert!(empty.is_empty());
+        assert_eq!(empty.head(), None);
+        assert!(empty.tail().is_empty());
+
+        let one = empty.prepend(1);
+        let two = one.prepend(2);
+        let other = one.prepend(3);
+        assert_eq!(two.head(), Some(&2));
+        assert_eq!(other.head(), Some(&3));
+        // nothing older changed
+        assert_eq!(one.head(), Some(&1));
+        assert!(empty.is_empty());
+
+        assert_eq!(two.tail().head(), Some(&1));
+        assert_eq!(other.tail().head(), Some(&1));
+        assert!(two.tail().tail().is_empty());
+        assert!(two.tail().tail().tail().is_empty());
+
+        // a chain of versions, every one of them still intact at the end
+        let versions: Vec<P> = (0..100).scan(P::default(), |list, i| {
+            *list = list.prepend(i);
+            Some(list.tail().prepend(i))
+        }).collect();
+        for (i, version) in versions.iter().enumerate() {
+            let mut list = version.tail();
+            assert_eq!(version.head(), Some(&(i as i32)));
+            for expected in (0..i as i32).rev() {
+                assert_eq!(list.head(), Some(&expected));
+                list = list.tail();
+            }
+            assert!(list.is_empty());
+        }
+    }
+
+    // generic over the element type as well, so every implementation gets leak-checked the same way
+    fn stack_no_leaks<S: Stack<DropCounter> + Default>() {
+        let tracker = DropTracker::new(4);
+        assert_no_leaks(|| {
+            let mut stack = S::default();
+            for id in 0..4 {
+                stack.push(tracker.counter(id));
+            }
+            assert_eq!(stack.pop().map(|elem| elem.id()), Some(3));
+        });
+        tracker.assert_all_dropped_once();
+    }
+
+    fn queue_no_leaks<Q: Queue<DropCounter> + Default>() {
+        let tracker = DropTracker::new(4);
+        assert_no_leaks(|| {
+            let mut queue = Q::default();
+            for id in 0..4 {
+                queue.enqueue(tracker.counter(id));
+            }
+            assert_eq!(queue.dequeue().map(|elem| elem.id()), Some(0));
+        });
+        tracker.assert_all_dropped_once();
+    }
+
+    #[test]
+    fn stacks() {
+        stack::<first::List<_>>();
+        stack::<second::List<_>>();
+        stack::<fourth::List<_>>();
+        stack::<sixth::List<_>>();
+
+        stack_no_leaks::<first::List<_>>();
+        stack_no_leaks::<second::List<_>>();
+        stack_no_leaks::<fourth::List<_>>();
+        stack_no_leaks::<sixth::List<_>>();
+    }
+
+    #[test]
+    fn queues() {
+        queue::<fourth::List<_>>();
+        queue::<fifth::List<_>>();
+        queue::<sixth::List<_>>();
+
+        queue_no_leaks::<fourth::List<_>>();
+        queue_no_leaks::<fifth::List<_>>();
+        queue_no_leaks::<sixth::List<_>>();
+    }
+
+    #[test]
+    fn deques() {
+        deque::<fourth::List<_>>();
+        deque::<sixth::List<_>>();
+
+        // all the traits are in scope here, and `front` on a list that is both a Queue and a Deque still means one thing
+        let mut list = fourth::List::new();
+        list.push_back(1); list.push_back(2);
+        assert_eq!(list.front().map(|elem| *elem), Some(1));
+        assert_eq!(list.back().map(|elem| *elem), Some(2));
+    }
+
+    #[test]
+    fn persistent_stacks() {
+        persistent_stack::<third::List<_>>();
+        persistent_stack::<third::sync::List<_>>();
+    }
+}